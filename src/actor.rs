@@ -1,54 +1,134 @@
 use tcod::{Color, Console};
-use tcod::colors;
 
 use description::*;
+use status::*;
+use entity::*;
+use event::*;
 use map::*;
 use world::*;
 use util::*;
+use equipment::EquipSlot;
+use spell::*;
 
-use std::fmt;
-use std::fmt::{Display, Formatter};
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Stats {
-  health     : u32,
-  max_health : u32,
-  speed      : u32
+  pub health     : u32,
+  pub max_health : u32,
+  pub speed      : u32,
+  pub attack     : u32,
+  pub defense    : u32
 }
 
+impl Stats {
+  /// The damage an actor with these stats takes from an attack of the given
+  /// strength. Every hit does at least one damage.
+  pub fn damage_taken_from( &self, attack : u32 ) -> u32 {
+    if attack > self.defense {
+      attack - self.defense
+    } else {
+      1
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Graphics {
   pub symbol : char,
-  pub fg     : Color,
-  pub bg     : Color
+  pub fg     : Color
 }
 
-pub enum ActionFailureReason<'a> {
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ActionFailureReason {
   BlockedByTile( Tile ),
-  BlockedByActor( &'a Actor )
+  BlockedByActor( Entity ),
+  CantInteract( Tile, Interaction ),
+  NothingToPickUp,
+  NoRangedWeapon,
+  NoThrowingWeapon,
+  CantEquip( Entity, EquipSlot ),
+  NothingEquipped( EquipSlot ),
+  CantUse( Entity ),
+  NotEnoughMana( SpellId ),
+  OutOfRange( SpellId ),
+  NotEnoughGold( Entity ),
+  CantSell( Entity ),
+  Unpaid( Entity )
 }
 
-impl<'a> Display for ActionFailureReason<'a> {
-  fn fmt( &self, fmtr : &mut Formatter ) -> Result<(), fmt::Error> {
+impl ActionFailureReason {
+  /// Describes the failure from the point of view of the player.
+  pub fn message( &self, world : &World ) -> String {
     use self::ActionFailureReason::*;
     
-    match self {
-      &BlockedByTile( t ) =>
-        write!( fmtr, "You were blocked by {}", t.description().name() ),
-      &BlockedByActor( a ) =>
-        write!( fmtr, "{} is in your way!", a.description().name() )
+    match *self {
+      BlockedByTile( t ) =>
+        format!( "You were blocked by {}", t.description().name() ),
+      BlockedByActor( a ) =>
+        format!( "{} is in your way!", capitalize( world.kind_of( a ).description().name() ) ),
+      CantInteract( t, Interaction::Open ) =>
+        format!( "You can't open {}", t.description().name() ),
+      CantInteract( t, Interaction::Close ) =>
+        format!( "You can't close {}", t.description().name() ),
+      NothingToPickUp =>
+        "There's nothing here to pick up".to_string(),
+      NoRangedWeapon =>
+        "You have nothing to shoot with".to_string(),
+      NoThrowingWeapon =>
+        "You have nothing to throw".to_string(),
+      CantEquip( item, slot ) =>
+        format!( "{} doesn't go there ({})"
+               , capitalize( &world.name_of( item ) )
+               , slot.name().to_lowercase() ),
+      NothingEquipped( slot ) =>
+        format!( "You have nothing equipped there ({})", slot.name().to_lowercase() ),
+      CantUse( item ) =>
+        format!( "You can't use {}", world.name_of( item ) ),
+      NotEnoughMana( id ) =>
+        format!( "You don't have enough mana to cast {}", spell( id ).description().name() ),
+      OutOfRange( id ) =>
+        format!( "That's out of range of {}", spell( id ).description().name() ),
+      NotEnoughGold( item ) =>
+        format!( "You can't afford {}", world.name_of( item ) ),
+      CantSell( item ) =>
+        format!( "Nobody wants to buy {}", world.name_of( item ) ),
+      Unpaid( item ) =>
+        format!( "You haven't paid for {} yet", world.name_of( item ) )
     }
   }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
-enum ActionKind {
+pub enum ActionKind {
   None,
-  MoveTo( Position )
+  MoveTo( Position ),
+  Interact( Position, Interaction ),
+  Attack( Entity ),
+  /// Trades places with a follower standing in the way
+  SwapPlaces( Entity ),
+  PickUp( Entity ),
+  /// Shoots or throws the given ranged weapon at a position
+  Fire( Entity, Position ),
+  /// Puts the given item from the inventory into the slot
+  Equip( Entity, EquipSlot ),
+  Unequip( EquipSlot ),
+  /// Uses up the given consumable item
+  Use( Entity ),
+  /// Casts the given spell at a position
+  Cast( SpellId, Position ),
+  /// Pays for an item that's for sale
+  Buy( Entity ),
+  /// Sells an item from the inventory to the given shopkeeper
+  Sell( Entity, Entity ),
+  Wait,
+  Rest,
+  Search
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Action {
   pub duration : u32,
-  kind : ActionKind
+  pub kind : ActionKind
 }
 
 impl Action {
@@ -61,74 +141,358 @@ impl Action {
   }
 }
 
+/// The part of an entity that lets it perform actions over time.
 pub struct Actor {
-  pub active   : bool,
   pub action   : Action,
-  pub pos      : Position,
-  pub stats    : Stats,
-  pub graphics : Graphics
-}
-
-impl Describe for Actor {
-  fn desc_id( &self ) -> String {
-    "placeholer".to_string()
-  }
+  pub effects  : StatusEffects,
+  /// How the actor gets around
+  pub movement : MovementType
 }
 
 impl Actor {
-  pub fn new( pos : Position ) -> Actor {
+  pub fn new( movement : MovementType ) -> Actor {
     Actor {
-      active: true,
       action: Action::none(),
-      pos: pos,
-      stats: Stats { health: 10, max_health: 10, speed: 100 },
-      graphics: Graphics { symbol: 'a'
-                         , fg: colors::WHITE
-                         , bg: colors::BLACK },
+      effects: StatusEffects::new(),
+      movement: movement
     }
   }
   
-  pub fn move_direction( &mut self, dir : Direction, world : &World )
-    -> Option<ActionFailureReason> {
-    
-    assert_eq!( self.action, Action::none() );
-    
-    let move_pos = dir.offset_position( self.pos, &world.map );
-    let tile = world.map.tile_at( move_pos );
-    
-    if tile.is_solid() {
-      Some( ActionFailureReason::BlockedByTile( tile ) )
-    } else {
-      self.action = Action {
-        duration: self.stats.speed,
-        kind: ActionKind::MoveTo( move_pos )
-      };
-      
-      None
-    }
+  /// The time it takes the actor to move, after status effects like haste
+  /// and slow have been taken into account.
+  pub fn effective_speed( &self, stats : &Stats ) -> u32 {
+    self.effects.modify_speed( stats.speed )
   }
   
-  pub fn update( &mut self, duration : u32 ) {
+  /// Advances the actor's action by `duration`, returning the kind of the
+  /// action if it was completed. Completed actions are carried out by the
+  /// `World`, since they can affect more than the actor itself.
+  pub fn update( &mut self, duration : u32, stats : &mut Stats )
+    -> Option<ActionKind> {
+    self.effects.update( duration, stats );
     
     if self.action.kind != ActionKind::None {
       if self.action.duration <= duration {
-        match self.action.kind {
-          ActionKind::MoveTo( pos ) => self.pos = pos,
-          _ => {}
+        let kind = self.action.kind;
+        
+        // Catching your breath heals a little
+        if kind == ActionKind::Rest && stats.health < stats.max_health {
+          stats.health += 1;
         }
         
         self.action = Action::none();
+        
+        return Some( kind )
       } else {
         self.action.duration -= duration;
       }
     }
+    
+    None
+  }
+}
+
+impl Graphics {
+  /// Draws the symbol on top of whatever is already drawn at `pos`, keeping
+  /// the background of the tile beneath it. The symbol is tinted by the light
+  /// falling on it.
+  pub fn render<C : Console>( &self, ctx : &mut C, pos : Position, light : Color ) {
+    let (x, y) = (pos.x as i32, pos.y as i32);
+    
+    ctx.set_char( x, y, self.symbol );
+    ctx.set_char_foreground( x, y, self.fg * light );
+  }
+}
+
+/// How many times longer than a step it takes to search the surroundings.
+pub const SEARCH_DURATION_FACTOR : u32 = 5;
+
+/// How many times longer than a step it takes to put on or take off gear.
+pub const EQUIP_DURATION_FACTOR : u32 = 2;
+
+// Starts the action `kind` for the entity, taking as long as a step would
+fn begin_action( world : &World, e : Entity, kind : ActionKind ) {
+  begin_long_action( world, e, kind, 1 );
+}
+
+// Starts the action `kind` for the entity, taking `factor` times as long as
+// a step would
+fn begin_long_action( world : &World, e : Entity, kind : ActionKind, factor : u32 ) {
+  let stats = world.effective_stats( e );
+  let duration = world.entities.actors.get( e ).expect( "entity is not an actor" )
+    .effective_speed( &stats ) * factor;
+  
+  begin_timed_action( world, e, kind, duration );
+}
+
+// Starts a step onto `pos` for the entity, taking as long as a step would
+// times the movement cost of the tile, given as a percentage
+fn begin_step( world : &World, e : Entity, pos : Position, cost : u32 ) {
+  let stats = world.effective_stats( e );
+  let speed = world.entities.actors.get( e ).expect( "entity is not an actor" )
+    .effective_speed( &stats );
+  let duration = ::std::cmp::max( speed * cost / NORMAL_MOVEMENT_COST, 1 );
+  
+  begin_timed_action( world, e, ActionKind::MoveTo( pos ), duration );
+}
+
+// Starts the action `kind` for the entity, taking exactly `duration`
+fn begin_timed_action( world : &World, e : Entity, kind : ActionKind, duration : u32 ) {
+  let mut actor = world.entities.actors.get_mut( e ).expect( "entity is not an actor" );
+  
+  assert_eq!( actor.action, Action::none() );
+  
+  actor.action = Action {
+    duration: duration,
+    kind: kind
+  };
+}
+
+// Reports the failure of an action, returning it
+fn fail( world : &World, e : Entity, reason : ActionFailureReason )
+  -> Option<ActionFailureReason> {
+  
+  world.emit( Event::ActionFailed { entity: e, reason: reason } );
+  
+  Some( reason )
+}
+
+pub fn move_direction( world : &World, e : Entity, dir : Direction )
+  -> Option<ActionFailureReason> {
+  
+  let pos = world.entities.position( e ).expect( "actor has no position" );
+  
+  // A confused actor stumbles in a random direction
+  let confused = world.entities.actors.get( e )
+    .map( |a| a.effects.has( StatusKind::Confusion ) )
+    .unwrap_or( false );
+  
+  let dir = if confused { Direction::random() } else { dir };
+  
+  let move_pos = dir.offset_position( pos, &world.map );
+  let tile = world.map.tile_at( move_pos );
+  
+  if let Some( other ) = world.actor_at( move_pos ) {
+    // Bumping into an enemy attacks it
+    if world.are_hostile( e, other ) {
+      begin_action( world, e, ActionKind::Attack( other ) );
+      
+      return None
+    }
+    
+    // Followers make way for their leader
+    if world.is_follower_of( other, e ) {
+      begin_action( world, e, ActionKind::SwapPlaces( other ) );
+      
+      return None
+    }
+    
+    return fail( world, e, ActionFailureReason::BlockedByActor( other ) )
+  }
+  
+  if tile.is_solid() {
+    // Bumping into something that can be opened, opens it
+    if tile.interact( Interaction::Open ).is_some() {
+      begin_action( world, e, ActionKind::Interact( move_pos, Interaction::Open ) );
+      
+      return None
+    }
+    
+    return fail( world, e, ActionFailureReason::BlockedByTile( tile ) )
+  }
+  
+  // Tiles like deep water can't be crossed by everyone
+  match world.movement_cost( e, move_pos ) {
+    Some( cost ) => {
+      begin_step( world, e, move_pos, cost );
+      
+      None
+    },
+    None => fail( world, e, ActionFailureReason::BlockedByTile( tile ) )
+  }
+}
+
+pub fn interact( world : &World, e : Entity, dir : Direction
+               , how : Interaction ) -> Option<ActionFailureReason> {
+  
+  let pos = world.entities.position( e ).expect( "actor has no position" );
+  let target = dir.offset_position( pos, &world.map );
+  let tile = world.map.tile_at( target );
+  
+  if tile.interact( how ).is_none() {
+    return fail( world, e, ActionFailureReason::CantInteract( tile, how ) )
+  }
+  
+  if how == Interaction::Close {
+    if let Some( other ) = world.actor_at( target ) {
+      return fail( world, e, ActionFailureReason::BlockedByActor( other ) )
+    }
+  }
+  
+  begin_action( world, e, ActionKind::Interact( target, how ) );
+  
+  None
+}
+
+/// Picks up the first item lying where the actor is standing.
+pub fn pick_up( world : &World, e : Entity ) -> Option<ActionFailureReason> {
+  let pos = world.entities.position( e ).expect( "actor has no position" );
+  
+  match world.items_at( pos ).first() {
+    Some( &item ) => pick_up_item( world, e, item ),
+    None => fail( world, e, ActionFailureReason::NothingToPickUp )
   }
+}
+
+/// Picks up the given item, which has to lie where the actor stands.
+pub fn pick_up_item( world : &World, e : Entity, item : Entity ) -> Option<ActionFailureReason> {
+  begin_action( world, e, ActionKind::PickUp( item ) );
   
-  pub fn render<C : Console>( &self, ctx : &mut C ) {
-    ctx.put_char_ex( self.pos.x as i32, self.pos.y as i32
-                   , self.graphics.symbol
-                   , self.graphics.fg
-                   , self.graphics.bg );
+  None
+}
+
+/// Finds a ranged weapon the actor has that's either thrown or shot,
+/// depending on `thrown`. A wielded weapon is shot before any in the
+/// inventory, but only weapons from the inventory are thrown.
+pub fn ranged_weapon( world : &World, e : Entity, thrown : bool ) -> Option<Entity> {
+  let is_suitable = |item : Entity| {
+    world.entities.ranged_weapons.get( item )
+      .map( |r| r.thrown == thrown )
+      .unwrap_or( false )
+  };
+  
+  let wielded = world.entities.equipment.get( e )
+    .and_then( |equipment| equipment.get( EquipSlot::Weapon ) );
+  
+  if let Some( weapon ) = wielded {
+    if !thrown && is_suitable( weapon ) {
+      return Some( weapon )
+    }
   }
+  
+  let inventory = match world.entities.inventories.get( e ) {
+    Some( inventory ) => inventory,
+    None => return None
+  };
+  
+  inventory.items.iter().cloned().find( |&item| is_suitable( item ) )
+}
+
+/// Shoots or throws the given ranged weapon at `target`.
+pub fn fire( world : &World, e : Entity, weapon : Entity, target : Position ) {
+  begin_action( world, e, ActionKind::Fire( weapon, target ) );
+}
+
+/// Puts on or wields the given item from the inventory.
+pub fn equip( world : &World, e : Entity, item : Entity, slot : EquipSlot )
+  -> Option<ActionFailureReason> {
+  
+  let fits = world.entities.equippables.get( item )
+    .map( |equip| equip.slots.contains( &slot ) )
+    .unwrap_or( false );
+  
+  if !fits {
+    return fail( world, e, ActionFailureReason::CantEquip( item, slot ) )
+  }
+  
+  begin_long_action( world, e, ActionKind::Equip( item, slot ), EQUIP_DURATION_FACTOR );
+  
+  None
+}
+
+/// Takes off whatever is in the given slot.
+pub fn unequip( world : &World, e : Entity, slot : EquipSlot )
+  -> Option<ActionFailureReason> {
+  
+  let equipped = world.entities.equipment.get( e )
+    .and_then( |equipment| equipment.get( slot ) )
+    .is_some();
+  
+  if !equipped {
+    return fail( world, e, ActionFailureReason::NothingEquipped( slot ) )
+  }
+  
+  begin_long_action( world, e, ActionKind::Unequip( slot ), EQUIP_DURATION_FACTOR );
+  
+  None
 }
 
+/// Uses up the given consumable item from the inventory.
+pub fn use_item( world : &World, e : Entity, item : Entity ) -> Option<ActionFailureReason> {
+  if !world.entities.consumables.has( item ) {
+    return fail( world, e, ActionFailureReason::CantUse( item ) )
+  }
+  
+  begin_action( world, e, ActionKind::Use( item ) );
+  
+  None
+}
+
+/// Casts a known spell at `target`, which is ignored by spells cast on
+/// oneself. Casting takes the spell's casting time.
+pub fn cast( world : &World, e : Entity, id : SpellId, target : Position )
+  -> Option<ActionFailureReason> {
+  
+  let spell = spell( id );
+  let pos = world.entities.position( e ).expect( "actor has no position" );
+  
+  let mana = world.entities.mana.get( e ).map( |m| m.current ).unwrap_or( 0 );
+  
+  if mana < spell.cost {
+    return fail( world, e, ActionFailureReason::NotEnoughMana( id ) )
+  }
+  
+  if spell.targeting != SpellTargeting::OnSelf && pos.distance( target ) > spell.range {
+    return fail( world, e, ActionFailureReason::OutOfRange( id ) )
+  }
+  
+  begin_timed_action( world, e, ActionKind::Cast( id, target ), spell.casting_time );
+  
+  None
+}
+
+/// Pays for an item that's for sale, taking it if it's still in the shop.
+pub fn buy( world : &World, e : Entity, item : Entity ) -> Option<ActionFailureReason> {
+  let price = world.entities.prices.get( item ).map( |p| p.0 ).unwrap_or( 0 );
+  let gold = world.entities.purses.get( e ).map( |p| p.gold ).unwrap_or( 0 );
+  
+  if gold < price {
+    return fail( world, e, ActionFailureReason::NotEnoughGold( item ) )
+  }
+  
+  begin_action( world, e, ActionKind::Buy( item ) );
+  
+  None
+}
+
+/// Sells an item from the inventory to the shopkeeper.
+pub fn sell( world : &World, e : Entity, item : Entity, keeper : Entity )
+  -> Option<ActionFailureReason> {
+  
+  if world.entities.for_sale.has( item ) {
+    return fail( world, e, ActionFailureReason::Unpaid( item ) )
+  }
+  
+  if !world.entities.prices.has( item ) {
+    return fail( world, e, ActionFailureReason::CantSell( item ) )
+  }
+  
+  begin_action( world, e, ActionKind::Sell( item, keeper ) );
+  
+  None
+}
+
+/// Lets a turn pass without doing anything.
+pub fn wait( world : &World, e : Entity ) {
+  begin_action( world, e, ActionKind::Wait );
+}
+
+/// Waits a turn while catching a breath, which heals a point of health.
+pub fn rest( world : &World, e : Entity ) {
+  begin_action( world, e, ActionKind::Rest );
+}
+
+/// Takes some time to look around for anything hidden nearby.
+pub fn search( world : &World, e : Entity ) {
+  begin_long_action( world, e, ActionKind::Search, SEARCH_DURATION_FACTOR );
+}
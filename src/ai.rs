@@ -0,0 +1,252 @@
+use world::*;
+use entity::*;
+use actor;
+use pathfind;
+use util::*;
+use spell::*;
+use noise::WAKING_LOUDNESS;
+
+use self::Behaviour::*;
+
+/// How an AI controlled entity decides what to do next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Behaviour {
+  /// Stays put
+  Idle,
+  /// Stumbles around at random
+  Wander,
+  /// Goes after the nearest hostile it can see, wandering otherwise
+  Hunt,
+  /// Goes after the nearest hostile it can see, staying close to its leader
+  /// otherwise
+  Follow,
+  /// Stays at its post, only fighting hostiles that come right up to it
+  Guard
+}
+
+impl Behaviour {
+  pub fn from_config( name : &str ) -> Option<Behaviour> {
+    Some( match name {
+      "idle" => Idle,
+      "wander" => Wander,
+      "hunt" => Hunt,
+      "follow" => Follow,
+      "guard" => Guard,
+      _ => return None
+    } )
+  }
+}
+
+pub struct Ai {
+  pub behaviour : Behaviour,
+  /// How far away the entity can spot others
+  pub sight     : u32,
+  /// A sleeping entity does nothing until a loud enough noise wakes it
+  pub asleep    : bool,
+  /// Where the entity last heard a noise it hasn't checked out yet
+  pub heard     : Option<Position>,
+  /// Who the entity follows around
+  pub leader    : Option<Entity>
+}
+
+impl Ai {
+  pub fn new( behaviour : Behaviour, sight : u32 ) -> Ai {
+    Ai {
+      behaviour: behaviour,
+      sight:     sight,
+      asleep:    false,
+      heard:     None,
+      leader:    None
+    }
+  }
+  
+  /// Reacts to a noise heard at `origin` with the given loudness, returning
+  /// whether it woke the entity up.
+  pub fn hear( &mut self, origin : Position, loudness : u32 ) -> bool {
+    if self.asleep {
+      if loudness < WAKING_LOUDNESS {
+        return false
+      }
+      
+      self.asleep = false;
+      self.heard = Some( origin );
+      
+      return true
+    }
+    
+    self.heard = Some( origin );
+    
+    false
+  }
+}
+
+/// Gives the entity something to do if it isn't already busy.
+pub fn update( world : &World, e : Entity ) {
+  let (behaviour, sight, asleep) = match world.entities.ais.get( e ) {
+    Some( ai ) => (ai.behaviour, ai.sight, ai.asleep),
+    None => return
+  };
+  
+  let busy = world.entities.actors.get( e )
+    .map( |a| a.action.is_active() )
+    .unwrap_or( true );
+  
+  if busy || asleep {
+    return
+  }
+  
+  match behaviour {
+    Idle => { investigate( world, e ); },
+    Wander => wander( world, e ),
+    Hunt => match visible_hostile( world, e, sight ) {
+      Some( target ) => if !cast_spell( world, e, target ) {
+        approach( world, e, target )
+      },
+      None => if !investigate( world, e ) {
+        wander( world, e )
+      }
+    },
+    Follow => match visible_hostile( world, e, sight ) {
+      Some( target ) => if !cast_spell( world, e, target ) {
+        approach( world, e, target )
+      },
+      None => follow( world, e )
+    },
+    Guard => match visible_hostile( world, e, 1 ) {
+      Some( target ) => approach( world, e, target ),
+      None => {}
+    }
+  }
+}
+
+fn wander( world : &World, e : Entity ) {
+  // Failing to move just means waiting for a better moment
+  actor::move_direction( world, e, Direction::random() );
+}
+
+fn approach( world : &World, e : Entity, target : Position ) {
+  let pos = world.entities.position( e ).expect( "AI entity has no position" );
+  
+  if let Some( dir ) = Direction::towards( pos, target ) {
+    actor::move_direction( world, e, dir );
+  }
+}
+
+// How close a follower tries to stay to its leader
+const FOLLOW_DISTANCE : u32 = 2;
+
+// Heads back to the entity's leader if it's fallen behind, going around
+// anything in the way
+fn follow( world : &World, e : Entity ) {
+  let leader = world.entities.ais.get( e ).and_then( |ai| ai.leader );
+  let pos = world.entities.position( e ).expect( "AI entity has no position" );
+  
+  let target = match leader.and_then( |leader| world.entities.position( leader ) ) {
+    Some( target ) => target,
+    None => return wander( world, e )
+  };
+  
+  if pos.distance( target ) <= FOLLOW_DISTANCE {
+    return
+  }
+  
+  let path = pathfind::find_path( &world.map, pos, target, |p| world.path_cost( e, p ) );
+  
+  let next = match path {
+    Some( path ) => path[ 0 ],
+    None => target
+  };
+  
+  if let Some( dir ) = Direction::towards( pos, next ) {
+    actor::move_direction( world, e, dir );
+  }
+}
+
+// Heads for the last noise the entity heard, forgetting about it once it's
+// there or can't get any closer. Returns whether there was anything to check
+// out.
+fn investigate( world : &World, e : Entity ) -> bool {
+  let heard = world.entities.ais.get( e ).and_then( |ai| ai.heard );
+  let pos = world.entities.position( e ).expect( "AI entity has no position" );
+  
+  let target = match heard {
+    Some( target ) => target,
+    None => return false
+  };
+  
+  let arrived = match Direction::towards( pos, target ) {
+    Some( dir ) => actor::move_direction( world, e, dir ).is_some(),
+    None => true
+  };
+  
+  if arrived {
+    if let Some( mut ai ) = world.entities.ais.get_mut( e ) {
+      ai.heard = None;
+    }
+  }
+  
+  true
+}
+
+// The chance in percent of a spellcaster calling for help when it could
+const SUMMON_CHANCE : u32 = 25;
+
+// Casts one of the entity's spells fitting the situation while fighting the
+// hostile at `target`, returning whether it started casting
+fn cast_spell( world : &World, e : Entity, target : Position ) -> bool {
+  let spells = match world.entities.spellbooks.get( e ) {
+    Some( book ) => book.spells.clone(),
+    None => return false
+  };
+  
+  let mana = world.entities.mana.get( e ).map( |m| m.current ).unwrap_or( 0 );
+  let pos = world.entities.position( e ).expect( "AI entity has no position" );
+  let stats = world.effective_stats( e );
+  let wounded = stats.health * 2 <= stats.max_health;
+  
+  for id in spells {
+    let spell = spell( id );
+    
+    if spell.cost > mana {
+      continue;
+    }
+    
+    let fitting = match spell.effect {
+      SpellEffect::Heal( _ ) => wounded,
+      SpellEffect::Summon( _ ) => roll_percent( SUMMON_CHANCE ),
+      SpellEffect::Damage( _ ) | SpellEffect::Status( _, _ ) =>
+        pos.distance( target ) <= spell.range && reaches( world, pos, target, spell.range ),
+      SpellEffect::Blink => false
+    };
+    
+    if fitting {
+      return actor::cast( world, e, id, target ).is_none()
+    }
+  }
+  
+  false
+}
+
+// Whether a bolt sent from `from` would reach `to` without hitting anything
+// else on the way
+fn reaches( world : &World, from : Position, to : Position, range : u32 ) -> bool {
+  world.projectile_path( from, to, range ).last() == Some( &to )
+}
+
+// The position of the nearest hostile the entity can see, if any
+fn visible_hostile( world : &World, e : Entity, sight : u32 ) -> Option<Position> {
+  let pos = match world.entities.position( e ) {
+    Some( pos ) => pos,
+    None => return None
+  };
+  
+  world.nearest_hostile( e, sight )
+    .and_then( |target| world.entities.position( target ) )
+    .and_then( |target| {
+      if world.map.visible_from( pos, sight ).contains( &target ) {
+        Some( target )
+      } else {
+        None
+      }
+    } )
+}
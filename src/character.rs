@@ -0,0 +1,308 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use tcod::Color;
+
+use util::*;
+use description::*;
+use actor::Stats;
+
+/// A class or background the player can pick for their character. Each
+/// changes the starting stats and gear of the character.
+#[derive(Clone)]
+pub struct CharacterOption {
+  desc_id       : String,
+  pub health    : i32,
+  pub attack    : i32,
+  pub defense   : i32,
+  /// Added to the time it takes to act
+  pub speed     : i32,
+  pub color     : Option<Color>,
+  /// The kinds of items the character starts out with equipped
+  pub equipment : Vec<String>,
+  /// The kinds of items the character starts out carrying
+  pub items     : Vec<String>,
+  /// The skill levels the character starts out with, keyed by skill name
+  pub skills    : HashMap<String, u32>,
+  pub mana      : i32,
+  /// The spells the character starts out knowing
+  pub spells    : Vec<String>,
+  /// The kind of monster that follows the character around as a pet
+  pub pet       : Option<String>,
+  /// The gold the character starts out with, on top of the base amount
+  pub gold      : u32
+}
+
+impl Describe for CharacterOption {
+  fn desc_id( &self ) -> String {
+    self.desc_id.clone()
+  }
+}
+
+#[derive(RustcDecodable)]
+struct CharacterOptionConfig {
+  health    : Option<i32>,
+  attack    : Option<i32>,
+  defense   : Option<i32>,
+  speed     : Option<i32>,
+  color     : Option<Rgb>,
+  equipment : Option<Vec<String>>,
+  items     : Option<Vec<String>>,
+  skills    : Option<HashMap<String, u32>>,
+  mana      : Option<i32>,
+  spells    : Option<Vec<String>>,
+  pet       : Option<String>,
+  gold      : Option<u32>
+}
+
+thread_local!( static CLASSES : RefCell<Vec<CharacterOption>> = RefCell::new( Vec::new() ) );
+thread_local!( static BACKGROUNDS : RefCell<Vec<CharacterOption>> = RefCell::new( Vec::new() ) );
+
+/// Loads the classes and backgrounds from `data/class.toml` and
+/// `data/background.toml`. Their names and descriptions are loaded from the
+/// same files along with the other descriptions.
+pub fn load_character_options() {
+  CLASSES.with( |classes| *classes.borrow_mut() = load_options_file( "class" ) );
+  BACKGROUNDS.with( |bgs| *bgs.borrow_mut() = load_options_file( "background" ) );
+}
+
+fn load_options_file( name : &str ) -> Vec<CharacterOption> {
+  use toml::decode;
+  
+  let filename = format!( "data/{}.toml", name );
+  let data = load_data_file( &filename );
+  
+  data.into_iter()
+    .map( |(entry_name, entry_value)| {
+      let config : CharacterOptionConfig = decode( entry_value )
+        .expect( &format!( "Invalid {} `{}` in '{}'", name, entry_name, filename ) );
+      
+      CharacterOption {
+        desc_id: format!( "{}.{}", name, entry_name ),
+        health: config.health.unwrap_or( 0 ),
+        attack: config.attack.unwrap_or( 0 ),
+        defense: config.defense.unwrap_or( 0 ),
+        speed: config.speed.unwrap_or( 0 ),
+        color: config.color.map( |c| c.into() ),
+        equipment: config.equipment.unwrap_or( Vec::new() ),
+        items: config.items.unwrap_or( Vec::new() ),
+        skills: config.skills.unwrap_or( HashMap::new() ),
+        mana: config.mana.unwrap_or( 0 ),
+        spells: config.spells.unwrap_or( Vec::new() ),
+        pet: config.pet,
+        gold: config.gold.unwrap_or( 0 )
+      }
+    } )
+    .collect()
+}
+
+pub fn classes() -> Vec<CharacterOption> {
+  CLASSES.with( |classes| classes.borrow().clone() )
+}
+
+pub fn backgrounds() -> Vec<CharacterOption> {
+  BACKGROUNDS.with( |bgs| bgs.borrow().clone() )
+}
+
+/// The stats every character starts out with, before their class and
+/// background are taken into account.
+pub const BASE_STATS : Stats =
+  Stats { health: 10, max_health: 10, speed: 100, attack: 3, defense: 0 };
+
+// Adds the bonus to a base stat, keeping it at a sensible minimum however
+// bad the picks are
+fn modified( base : u32, bonus : i32, min : u32 ) -> u32 {
+  let value = base as i32 + bonus;
+  
+  if value < min as i32 { min } else { value as u32 }
+}
+
+/// The mana every character starts out with, before their class and
+/// background are taken into account.
+pub const BASE_MANA : u32 = 4;
+
+/// The gold every character starts out with.
+pub const BASE_GOLD : u32 = 10;
+
+/// Who the player is playing as.
+#[derive(Clone)]
+pub struct Character {
+  pub name       : String,
+  pub class      : CharacterOption,
+  pub background : CharacterOption
+}
+
+impl Character {
+  pub fn new( name : String, class : CharacterOption, background : CharacterOption )
+    -> Character {
+    
+    Character {
+      name: name,
+      class: class,
+      background: background
+    }
+  }
+  
+  /// The stats the character starts out with.
+  pub fn stats( &self ) -> Stats {
+    let (class, bg) = (&self.class, &self.background);
+    let health = modified( BASE_STATS.health, class.health + bg.health, 1 );
+    
+    Stats {
+      health: health,
+      max_health: health,
+      speed: modified( BASE_STATS.speed, class.speed + bg.speed, 1 ),
+      attack: modified( BASE_STATS.attack, class.attack + bg.attack, 0 ),
+      defense: modified( BASE_STATS.defense, class.defense + bg.defense, 0 )
+    }
+  }
+  
+  /// The most mana the character starts out with.
+  pub fn mana( &self ) -> u32 {
+    modified( BASE_MANA, self.class.mana + self.background.mana, 0 )
+  }
+  
+  /// The spells the character starts out knowing.
+  pub fn spells( &self ) -> Vec<String> {
+    let mut spells = self.class.spells.clone();
+    
+    for spell in &self.background.spells {
+      if !spells.contains( spell ) {
+        spells.push( spell.clone() );
+      }
+    }
+    
+    spells
+  }
+  
+  /// The colour of the character's glyph. The background's colour wins over
+  /// the class's, if it has one.
+  pub fn color( &self ) -> Color {
+    self.background.color.or( self.class.color ).unwrap_or( ::tcod::colors::WHITE )
+  }
+  
+  /// The gold the character starts out with.
+  pub fn gold( &self ) -> u32 {
+    BASE_GOLD + self.class.gold + self.background.gold
+  }
+  
+  /// The kind of pet the character starts out with, if any. The
+  /// background's pet wins over the class's.
+  pub fn pet( &self ) -> Option<String> {
+    self.background.pet.clone().or( self.class.pet.clone() )
+  }
+  
+  /// The kinds of items the character starts out with equipped.
+  pub fn equipment( &self ) -> Vec<String> {
+    self.class.equipment.iter().chain( self.background.equipment.iter() ).cloned().collect()
+  }
+  
+  /// The kinds of items the character starts out carrying.
+  pub fn items( &self ) -> Vec<String> {
+    self.class.items.iter().chain( self.background.items.iter() ).cloned().collect()
+  }
+  
+  /// The skill levels the character starts out with, keyed by skill name.
+  pub fn skills( &self ) -> HashMap<String, u32> {
+    let mut skills = self.class.skills.clone();
+    
+    for (name, &level) in &self.background.skills {
+      *skills.entry( name.clone() ).or_insert( 0 ) += level;
+    }
+    
+    skills
+  }
+  
+  /// The name along with the class, like "Aria the Fighter".
+  pub fn title( &self ) -> String {
+    format!( "{} the {}", self.name, self.class.description().name() )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tcod::colors;
+  
+  // The first class and background, without any bonuses or gear
+  fn plain_options() -> (CharacterOption, CharacterOption) {
+    load_character_options();
+    
+    let mut class = classes()[ 0 ].clone();
+    let mut background = backgrounds()[ 0 ].clone();
+    
+    for option in vec![ &mut class, &mut background ] {
+      option.health = 0;
+      option.attack = 0;
+      option.defense = 0;
+      option.speed = 0;
+      option.color = None;
+      option.equipment.clear();
+      option.items.clear();
+    }
+    
+    (class, background)
+  }
+  
+  fn character( class : CharacterOption, background : CharacterOption ) -> Character {
+    Character::new( "Tester".to_string(), class, background )
+  }
+  
+  #[test]
+  fn plain_picks_keep_the_base_stats() {
+    let (class, background) = plain_options();
+    
+    assert_eq!( character( class, background ).stats(), BASE_STATS );
+  }
+  
+  #[test]
+  fn class_and_background_bonuses_add_up() {
+    let (mut class, mut background) = plain_options();
+    class.health = 4;
+    class.attack = 1;
+    class.speed = -10;
+    background.health = -2;
+    background.defense = 2;
+    background.speed = 5;
+    
+    assert_eq!( character( class, background ).stats()
+              , Stats { health: 12, max_health: 12, speed: 95, attack: 4, defense: 2 } );
+  }
+  
+  #[test]
+  fn stats_stay_sensible_whatever_the_picks() {
+    let (mut class, mut background) = plain_options();
+    class.health = -100;
+    class.attack = -10;
+    background.defense = -10;
+    background.speed = -1000;
+    
+    assert_eq!( character( class, background ).stats()
+              , Stats { health: 1, max_health: 1, speed: 1, attack: 0, defense: 0 } );
+  }
+  
+  #[test]
+  fn background_colour_wins_over_the_class_colour() {
+    let (mut class, mut background) = plain_options();
+    assert_eq!( character( class.clone(), background.clone() ).color(), colors::WHITE );
+    
+    class.color = Some( colors::RED );
+    assert_eq!( character( class.clone(), background.clone() ).color(), colors::RED );
+    
+    background.color = Some( colors::BLUE );
+    assert_eq!( character( class, background ).color(), colors::BLUE );
+  }
+  
+  #[test]
+  fn gear_comes_from_both_picks() {
+    let (mut class, mut background) = plain_options();
+    class.equipment = vec![ "dagger".to_string() ];
+    class.items = vec![ "ration".to_string() ];
+    background.equipment = vec![ "buckler".to_string() ];
+    background.items = vec![ "coin".to_string() ];
+    
+    let character = character( class, background );
+    assert_eq!( character.equipment(), vec![ "dagger".to_string(), "buckler".to_string() ] );
+    assert_eq!( character.items(), vec![ "ration".to_string(), "coin".to_string() ] );
+  }
+}
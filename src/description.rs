@@ -31,6 +31,10 @@ impl Description {
   pub fn name( &self ) -> &str {
     &self.name
   }
+  
+  pub fn description( &self ) -> &str {
+    &self.description
+  }
 }
 
 pub trait Describe {
@@ -58,6 +62,15 @@ pub fn load_descriptions() {
   DESCRIPTIONS.with( |descs| {
         let mut d = descs.borrow_mut();
         load_file( "tile", &mut d );
+        load_file( "status", &mut d );
+        load_file( "actor", &mut d );
+        load_file( "monster", &mut d );
+        load_file( "item", &mut d );
+        load_file( "class", &mut d );
+        load_file( "background", &mut d );
+        load_file( "skill", &mut d );
+        load_file( "spell", &mut d );
+        load_file( "quest", &mut d );
       } );
 }
 
@@ -0,0 +1,199 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use util::*;
+use quest::{QuestId, QuestStatus, quest_id};
+
+/// Lets the player talk to the entity, starting the dialogue of the given
+/// name.
+pub struct Speaker {
+  pub dialogue : String
+}
+
+/// What has to hold for a dialogue choice to be offered.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Condition {
+  /// The player carries an item of the given kind
+  HasItem( String ),
+  /// The quest is at the given stage
+  Quest( QuestId, QuestStatus )
+}
+
+/// What picking a dialogue choice does.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DialogueEffect {
+  /// Hands the player a new item of the given kind
+  GiveItem( String ),
+  /// Takes an item of the given kind from the player
+  TakeItem( String ),
+  StartQuest( QuestId ),
+  /// Puts the speaker into the faction of the given name
+  ChangeFaction( String )
+}
+
+#[derive(Clone, Debug)]
+pub struct Choice {
+  pub text      : String,
+  /// The node the conversation goes on with, or `None` if it ends
+  pub next      : Option<String>,
+  pub condition : Option<Condition>,
+  pub effects   : Vec<DialogueEffect>
+}
+
+/// Something the speaker says, along with what the player can answer.
+#[derive(Clone, Debug)]
+pub struct DialogueNode {
+  pub text    : String,
+  pub choices : Vec<Choice>
+}
+
+/// A conversation as defined in `data/dialogue.toml`.
+#[derive(Clone, Debug)]
+pub struct Dialogue {
+  pub start : String,
+  nodes     : HashMap<String, DialogueNode>
+}
+
+impl Dialogue {
+  pub fn node( &self, name : &str ) -> &DialogueNode {
+    self.nodes.get( name ).expect( &format!( "No dialogue node `{}` exists", name ) )
+  }
+}
+
+#[derive(RustcDecodable)]
+struct ConditionConfig {
+  has_item : Option<String>,
+  quest    : Option<String>,
+  status   : Option<String>
+}
+
+impl ConditionConfig {
+  fn into_condition( self ) -> Option<Condition> {
+    if let Some( kind ) = self.has_item {
+      return Some( Condition::HasItem( kind ) )
+    }
+    
+    let quest = self.quest.as_ref().and_then( |q| quest_id( q ) );
+    let status = self.status.as_ref().and_then( |s| QuestStatus::from_config( s ) );
+    
+    match (quest, status) {
+      (Some( quest ), Some( status )) => Some( Condition::Quest( quest, status ) ),
+      _ => None
+    }
+  }
+}
+
+#[derive(RustcDecodable)]
+struct EffectConfig {
+  give_item   : Option<String>,
+  take_item   : Option<String>,
+  start_quest : Option<String>,
+  faction     : Option<String>
+}
+
+impl EffectConfig {
+  fn into_effect( self ) -> Option<DialogueEffect> {
+    Some( if let Some( kind ) = self.give_item {
+      DialogueEffect::GiveItem( kind )
+    } else if let Some( kind ) = self.take_item {
+      DialogueEffect::TakeItem( kind )
+    } else if let Some( quest ) = self.start_quest {
+      match quest_id( &quest ) {
+        Some( id ) => DialogueEffect::StartQuest( id ),
+        None => return None
+      }
+    } else if let Some( faction ) = self.faction {
+      DialogueEffect::ChangeFaction( faction )
+    } else {
+      return None
+    } )
+  }
+}
+
+#[derive(RustcDecodable)]
+struct ChoiceConfig {
+  text      : String,
+  next      : Option<String>,
+  condition : Option<ConditionConfig>,
+  effects   : Option<Vec<EffectConfig>>
+}
+
+#[derive(RustcDecodable)]
+struct NodeConfig {
+  text    : String,
+  choices : Vec<ChoiceConfig>
+}
+
+#[derive(RustcDecodable)]
+struct DialogueConfig {
+  start : String,
+  nodes : HashMap<String, NodeConfig>
+}
+
+thread_local!( static DIALOGUES : RefCell<HashMap<String, Dialogue>> =
+  RefCell::new( HashMap::new() ) );
+
+/// Loads the conversations from `data/dialogue.toml`. The quests they refer
+/// to have to be loaded first.
+pub fn load_dialogues() {
+  use toml::decode;
+  
+  let filename = "data/dialogue.toml";
+  let data = load_data_file( filename );
+  
+  DIALOGUES.with( |dialogues| {
+    let mut d = dialogues.borrow_mut();
+    
+    for (entry_name, entry_value) in data.into_iter() {
+      let config : DialogueConfig = decode( entry_value )
+        .expect( &format!( "Invalid dialogue `{}` in '{}'", entry_name, filename ) );
+      
+      let mut nodes = HashMap::new();
+      
+      for (node_name, node) in config.nodes {
+        let choices = node.choices.into_iter()
+          .map( |choice| {
+            let condition = choice.condition.map( |c| {
+              c.into_condition()
+                .expect( &format!( "Invalid condition in node `{}` of dialogue `{}`"
+                                 , node_name, entry_name ) )
+            } );
+            
+            let effects = choice.effects.unwrap_or( Vec::new() ).into_iter()
+              .map( |e| {
+                e.into_effect()
+                  .expect( &format!( "Invalid effect in node `{}` of dialogue `{}`"
+                                   , node_name, entry_name ) )
+              } )
+              .collect();
+            
+            Choice {
+              text: choice.text,
+              next: choice.next,
+              condition: condition,
+              effects: effects
+            }
+          } )
+          .collect();
+        
+        nodes.insert( node_name, DialogueNode { text: node.text, choices: choices } );
+      }
+      
+      let dialogue = Dialogue { start: config.start, nodes: nodes };
+      
+      for name in dialogue.nodes.values()
+                    .flat_map( |n| n.choices.iter() )
+                    .filter_map( |c| c.next.as_ref() )
+                    .chain( Some( &dialogue.start ) ) {
+        assert!( dialogue.nodes.contains_key( name )
+               , "The dialogue `{}` leads to the unknown node `{}`", entry_name, name );
+      }
+      
+      d.insert( entry_name, dialogue );
+    }
+  } );
+}
+
+pub fn dialogue( name : &str ) -> Option<Dialogue> {
+  DIALOGUES.with( |dialogues| dialogues.borrow().get( name ).cloned() )
+}
@@ -0,0 +1,265 @@
+use std::cell::{RefCell, Ref, RefMut};
+use std::iter::Enumerate;
+use std::slice;
+
+use util::*;
+use description::*;
+use actor::*;
+use light::LightSource;
+use ai::Ai;
+use equipment::{Equipment, Equippable};
+use item::Consumable;
+use hunger::Hunger;
+use experience::{Experience, ExperienceReward};
+use skill::Skills;
+use spell::{Mana, Spellbook};
+use faction::Faction;
+use shop::{Purse, Price, ForSale, Shopkeeper};
+use dialogue::Speaker;
+
+/// Identifies an entity in the world. Entities are nothing more than an ID,
+/// everything else about them is stored in components keyed by that ID.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Entity( pub u32 );
+
+/// Storage for one kind of component, indexed by entity.
+pub struct Components<T> {
+  data : Vec<Option<RefCell<T>>>
+}
+
+impl<T> Components<T> {
+  pub fn new() -> Components<T> {
+    Components {
+      data: Vec::new()
+    }
+  }
+  
+  pub fn insert( &mut self, e : Entity, value : T ) {
+    let idx = e.0 as usize;
+    
+    while self.data.len() <= idx {
+      self.data.push( None );
+    }
+    
+    self.data[ idx ] = Some( RefCell::new( value ) );
+  }
+  
+  pub fn remove( &mut self, e : Entity ) -> Option<T> {
+    let idx = e.0 as usize;
+    
+    if idx < self.data.len() {
+      self.data[ idx ].take().map( |c| c.into_inner() )
+    } else {
+      None
+    }
+  }
+  
+  fn cell( &self, e : Entity ) -> Option<&RefCell<T>> {
+    self.data.get( e.0 as usize ).and_then( |c| c.as_ref() )
+  }
+  
+  pub fn has( &self, e : Entity ) -> bool {
+    self.cell( e ).is_some()
+  }
+  
+  pub fn get( &self, e : Entity ) -> Option<Ref<T>> {
+    self.cell( e ).map( |c| c.borrow() )
+  }
+  
+  pub fn get_mut( &self, e : Entity ) -> Option<RefMut<T>> {
+    self.cell( e ).map( |c| c.borrow_mut() )
+  }
+  
+  pub fn iter( &self ) -> Iter<T> {
+    Iter {
+      inner: self.data.iter().enumerate()
+    }
+  }
+  
+  /// The entities that have this component.
+  pub fn entities( &self ) -> Vec<Entity> {
+    self.iter().map( |(e, _)| e ).collect()
+  }
+}
+
+pub struct Iter<'a, T : 'a> {
+  inner : Enumerate<slice::Iter<'a, Option<RefCell<T>>>>
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+  type Item = (Entity, &'a RefCell<T>);
+  
+  fn next( &mut self ) -> Option<(Entity, &'a RefCell<T>)> {
+    while let Some( (i, c) ) = self.inner.next() {
+      if let Some( ref cell ) = *c {
+        return Some( (Entity( i as u32 ), cell) )
+      }
+    }
+    
+    None
+  }
+}
+
+/// What kind of thing an entity is, used to look up its description.
+pub struct Kind {
+  pub desc_id : String
+}
+
+impl Kind {
+  pub fn new( desc_id : &str ) -> Kind {
+    Kind {
+      desc_id: desc_id.to_string()
+    }
+  }
+}
+
+impl Describe for Kind {
+  fn desc_id( &self ) -> String {
+    self.desc_id.clone()
+  }
+}
+
+/// Marks the entity as being controlled by the player's input.
+pub struct PlayerControlled;
+
+/// Marks the entity as an item that can be picked up and carried around.
+pub struct Item;
+
+/// Lets an item be used to attack from a distance, either by firing it or by
+/// throwing the item itself.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, RustcDecodable)]
+pub struct RangedWeapon {
+  pub range  : u32,
+  pub damage : u32,
+  pub thrown : bool
+}
+
+/// Hides what kind of item an entity is until its kind is identified.
+pub struct Unidentified {
+  /// The kind of item, as named in `data/item.toml`
+  pub kind : String,
+  /// The name shown in the meantime
+  pub name : String
+}
+
+pub struct Inventory {
+  pub items : Vec<Entity>
+}
+
+impl Inventory {
+  pub fn new() -> Inventory {
+    Inventory {
+      items: Vec::new()
+    }
+  }
+}
+
+/// All the entities of the world and their components.
+pub struct Entities {
+  next_id               : u32,
+  pub kinds             : Components<Kind>,
+  pub positions         : Components<Position>,
+  pub graphics          : Components<Graphics>,
+  pub stats             : Components<Stats>,
+  pub actors            : Components<Actor>,
+  pub lights            : Components<LightSource>,
+  pub inventories       : Components<Inventory>,
+  pub items             : Components<Item>,
+  pub ranged_weapons    : Components<RangedWeapon>,
+  pub equippables       : Components<Equippable>,
+  pub equipment         : Components<Equipment>,
+  pub consumables       : Components<Consumable>,
+  pub unidentified      : Components<Unidentified>,
+  pub hunger            : Components<Hunger>,
+  pub experience        : Components<Experience>,
+  pub rewards           : Components<ExperienceReward>,
+  pub skills            : Components<Skills>,
+  pub mana              : Components<Mana>,
+  pub spellbooks        : Components<Spellbook>,
+  pub factions          : Components<Faction>,
+  pub purses            : Components<Purse>,
+  pub prices            : Components<Price>,
+  pub for_sale          : Components<ForSale>,
+  pub shopkeepers       : Components<Shopkeeper>,
+  pub speakers          : Components<Speaker>,
+  pub ais               : Components<Ai>,
+  pub player_controlled : Components<PlayerControlled>
+}
+
+impl Entities {
+  pub fn new() -> Entities {
+    Entities {
+      next_id:           0,
+      kinds:             Components::new(),
+      positions:         Components::new(),
+      graphics:          Components::new(),
+      stats:             Components::new(),
+      actors:            Components::new(),
+      lights:            Components::new(),
+      inventories:       Components::new(),
+      items:             Components::new(),
+      ranged_weapons:    Components::new(),
+      equippables:       Components::new(),
+      equipment:         Components::new(),
+      consumables:       Components::new(),
+      unidentified:      Components::new(),
+      hunger:            Components::new(),
+      experience:        Components::new(),
+      rewards:           Components::new(),
+      skills:            Components::new(),
+      mana:              Components::new(),
+      spellbooks:        Components::new(),
+      factions:          Components::new(),
+      purses:            Components::new(),
+      prices:            Components::new(),
+      for_sale:          Components::new(),
+      shopkeepers:       Components::new(),
+      speakers:          Components::new(),
+      ais:               Components::new(),
+      player_controlled: Components::new()
+    }
+  }
+  
+  /// Creates a new entity without any components.
+  pub fn create( &mut self ) -> Entity {
+    let e = Entity( self.next_id );
+    self.next_id += 1;
+    
+    e
+  }
+  
+  /// Removes every component of the entity.
+  pub fn destroy( &mut self, e : Entity ) {
+    self.kinds.remove( e );
+    self.positions.remove( e );
+    self.graphics.remove( e );
+    self.stats.remove( e );
+    self.actors.remove( e );
+    self.lights.remove( e );
+    self.inventories.remove( e );
+    self.items.remove( e );
+    self.ranged_weapons.remove( e );
+    self.equippables.remove( e );
+    self.equipment.remove( e );
+    self.consumables.remove( e );
+    self.unidentified.remove( e );
+    self.hunger.remove( e );
+    self.experience.remove( e );
+    self.rewards.remove( e );
+    self.skills.remove( e );
+    self.mana.remove( e );
+    self.spellbooks.remove( e );
+    self.factions.remove( e );
+    self.purses.remove( e );
+    self.prices.remove( e );
+    self.for_sale.remove( e );
+    self.shopkeepers.remove( e );
+    self.speakers.remove( e );
+    self.ais.remove( e );
+    self.player_controlled.remove( e );
+  }
+  
+  pub fn position( &self, e : Entity ) -> Option<Position> {
+    self.positions.get( e ).map( |p| *p )
+  }
+}
@@ -0,0 +1,89 @@
+use std::collections::HashMap;
+
+use entity::Entity;
+
+/// The places on an actor where gear can be worn or wielded.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum EquipSlot {
+  Weapon,
+  OffHand,
+  Body,
+  Head,
+  LeftRing,
+  RightRing
+}
+
+/// Every slot, in the order they're listed on the equipment screen.
+pub const EQUIP_SLOTS : [EquipSlot; 6] =
+  [ EquipSlot::Weapon, EquipSlot::OffHand, EquipSlot::Body, EquipSlot::Head
+  , EquipSlot::LeftRing, EquipSlot::RightRing ];
+
+impl EquipSlot {
+  /// The slots an item configured with the given slot name fits into.
+  pub fn from_config( name : &str ) -> Option<Vec<EquipSlot>> {
+    use self::EquipSlot::*;
+    
+    Some( match name {
+      "weapon"   => vec![ Weapon ],
+      "off_hand" => vec![ OffHand ],
+      "body"     => vec![ Body ],
+      "head"     => vec![ Head ],
+      "ring"     => vec![ LeftRing, RightRing ],
+      _          => return None
+    } )
+  }
+  
+  pub fn name( self ) -> &'static str {
+    use self::EquipSlot::*;
+    
+    match self {
+      Weapon    => "Weapon",
+      OffHand   => "Off-hand",
+      Body      => "Body",
+      Head      => "Head",
+      LeftRing  => "Left ring",
+      RightRing => "Right ring"
+    }
+  }
+}
+
+/// Lets an item be equipped, changing the stats of whoever wears it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Equippable {
+  pub slots   : Vec<EquipSlot>,
+  pub attack  : u32,
+  pub defense : u32,
+  /// Added to the time it takes to act, so heavy gear slows its wearer down
+  pub speed   : i32
+}
+
+/// The items an actor has equipped, by slot. Equipped items aren't part of
+/// the actor's inventory.
+pub struct Equipment {
+  slots : HashMap<EquipSlot, Entity>
+}
+
+impl Equipment {
+  pub fn new() -> Equipment {
+    Equipment {
+      slots: HashMap::new()
+    }
+  }
+  
+  pub fn get( &self, slot : EquipSlot ) -> Option<Entity> {
+    self.slots.get( &slot ).cloned()
+  }
+  
+  /// Puts the item in the slot, returning the item it replaced.
+  pub fn equip( &mut self, slot : EquipSlot, item : Entity ) -> Option<Entity> {
+    self.slots.insert( slot, item )
+  }
+  
+  pub fn unequip( &mut self, slot : EquipSlot ) -> Option<Entity> {
+    self.slots.remove( &slot )
+  }
+  
+  pub fn items( &self ) -> Vec<Entity> {
+    EQUIP_SLOTS.iter().filter_map( |&slot| self.get( slot ) ).collect()
+  }
+}
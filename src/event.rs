@@ -0,0 +1,109 @@
+use util::*;
+use entity::Entity;
+use actor::ActionFailureReason;
+use world::World;
+use item::UseEffect;
+use hunger::HungerStage;
+use skill::SkillKind;
+use spell::SpellId;
+use quest::QuestId;
+use trap::TrapKind;
+
+/// Something that happened in the world. Events are queued up by the world
+/// as they happen and handed to every `EventListener` once the world is
+/// done updating.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+  Moved { entity : Entity, from : Position, to : Position },
+  ActionFailed { entity : Entity, reason : ActionFailureReason },
+  /// `entity` traded places with `other`, which was in its way
+  SwappedPlaces { entity : Entity, other : Entity },
+  Attacked { attacker : Entity, target : Entity, damage : u32 },
+  Missed { attacker : Entity, target : Entity },
+  Died { entity : Entity, killer : Option<Entity> },
+  PickedUp { entity : Entity, item : Entity },
+  Fired { entity : Entity, weapon : Entity, hit : Option<Entity> },
+  Equipped { entity : Entity, item : Entity },
+  Unequipped { entity : Entity, item : Entity },
+  Used { entity : Entity, item : Entity, effect : UseEffect },
+  HungerChanged { entity : Entity, stage : HungerStage },
+  LevelledUp { entity : Entity, level : u32 },
+  SkillImproved { entity : Entity, skill : SkillKind, level : u32 },
+  /// A spell was worked, affecting `target` if it reached anyone
+  Cast { entity : Entity, spell : SpellId, target : Option<Entity> },
+  SpellFailed { entity : Entity, spell : SpellId },
+  Bought { entity : Entity, item : Entity, price : u32 },
+  Sold { entity : Entity, item : Entity, price : u32 },
+  /// `entity` left the shop of `keeper` without paying
+  Robbed { entity : Entity, keeper : Entity },
+  /// `from` gave `item` to `entity`
+  Received { entity : Entity, item : Entity, from : Entity },
+  /// `entity` gave `item` to `to`
+  HandedOver { entity : Entity, item : Entity, to : Entity },
+  QuestStarted { quest : QuestId },
+  /// An objective of the quest, by its index, was completed
+  ObjectiveCompleted { quest : QuestId, objective : usize },
+  QuestCompleted { quest : QuestId },
+  /// `entity` was handed `item` for completing a quest
+  Rewarded { entity : Entity, item : Entity },
+  /// A peaceful actor turned on the player
+  TurnedHostile { entity : Entity },
+  /// A sleeping actor was woken up by a noise
+  WokeUp { entity : Entity },
+  Searched { entity : Entity, found : u32 },
+  /// `entity` stepped on a trap and set it off
+  TrapSprung { entity : Entity, trap : TrapKind },
+  LevelEntered { depth : u32 }
+}
+
+/// Anything that wants to react to what happens in the world.
+pub trait EventListener {
+  fn on_event( &mut self, world : &World, event : &Event );
+}
+
+/// Keeps count of what the player has been up to.
+pub struct Statistics {
+  pub steps_taken    : u32,
+  pub damage_dealt   : u32,
+  pub damage_taken   : u32,
+  pub kills          : u32,
+  pub items_picked   : u32,
+  pub deepest_level  : u32
+}
+
+impl Statistics {
+  pub fn new() -> Statistics {
+    Statistics {
+      steps_taken:   0,
+      damage_dealt:  0,
+      damage_taken:  0,
+      kills:         0,
+      items_picked:  0,
+      deepest_level: 0
+    }
+  }
+}
+
+impl EventListener for Statistics {
+  fn on_event( &mut self, world : &World, event : &Event ) {
+    use self::Event::*;
+    
+    let player = world.player();
+    
+    match *event {
+      Moved { entity, .. } if entity == player =>
+        self.steps_taken += 1,
+      Attacked { attacker, damage, .. } if attacker == player =>
+        self.damage_dealt += damage,
+      Attacked { target, damage, .. } if target == player =>
+        self.damage_taken += damage,
+      Died { killer: Some( killer ), .. } if killer == player =>
+        self.kills += 1,
+      PickedUp { entity, .. } if entity == player =>
+        self.items_picked += 1,
+      LevelEntered { depth } if depth > self.deepest_level =>
+        self.deepest_level = depth,
+      _ => {}
+    }
+  }
+}
@@ -0,0 +1,161 @@
+use std::cell::RefCell;
+
+use util::*;
+
+#[derive(RustcDecodable)]
+struct LevelConfig {
+  thresholds       : Vec<u32>,
+  health_per_level : u32
+}
+
+thread_local!( static LEVELS : RefCell<LevelConfig> =
+  RefCell::new( LevelConfig { thresholds: Vec::new(), health_per_level: 0 } ) );
+
+/// Loads the experience needed for each level from `data/experience.toml`.
+pub fn load_levels() {
+  use toml::{decode, Value};
+  
+  let filename = "data/experience.toml";
+  let data = load_data_file( filename );
+  
+  let config : LevelConfig = decode( Value::Table( data ) )
+    .expect( &format!( "Invalid experience levels in '{}'", filename ) );
+  
+  LEVELS.with( |levels| *levels.borrow_mut() = config );
+}
+
+/// The total experience needed to reach the level after `level`, or `None`
+/// if it's the highest one.
+pub fn next_threshold( level : u32 ) -> Option<u32> {
+  LEVELS.with( |levels| levels.borrow().thresholds.get( level as usize - 1 ).cloned() )
+}
+
+/// How much maximum health is gained with each level.
+pub fn health_per_level() -> u32 {
+  LEVELS.with( |levels| levels.borrow().health_per_level )
+}
+
+/// The experience gained by killing the entity.
+pub struct ExperienceReward {
+  pub points : u32
+}
+
+/// The experience an actor has gathered, and the level it got it to.
+pub struct Experience {
+  pub level   : u32,
+  pub points  : u32,
+  /// Stat increases earned by levelling up that haven't been picked yet
+  pub pending : u32
+}
+
+impl Experience {
+  pub fn new() -> Experience {
+    Experience {
+      level: 1,
+      points: 0,
+      pending: 0
+    }
+  }
+  
+  /// Adds experience, returning how many levels were gained by it.
+  pub fn gain( &mut self, points : u32 ) -> u32 {
+    self.points += points;
+    
+    let mut gained = 0;
+    
+    while let Some( threshold ) = next_threshold( self.level ) {
+      if self.points < threshold {
+        break;
+      }
+      
+      self.level += 1;
+      self.pending += 1;
+      gained += 1;
+    }
+    
+    gained
+  }
+}
+
+/// The stat increases to pick from on levelling up.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StatIncrease {
+  Attack,
+  Defense,
+  Health,
+  Speed
+}
+
+/// Every stat increase, in the order they're offered.
+pub const STAT_INCREASES : [StatIncrease; 4] =
+  [ StatIncrease::Attack, StatIncrease::Defense, StatIncrease::Health, StatIncrease::Speed ];
+
+/// How much maximum health the health increase grants.
+pub const HEALTH_INCREASE : u32 = 4;
+
+/// How much faster the speed increase makes its actor act.
+pub const SPEED_INCREASE : u32 = 5;
+
+impl StatIncrease {
+  pub fn name( self ) -> String {
+    match self {
+      StatIncrease::Attack  => "Attack +1".to_string(),
+      StatIncrease::Defense => "Defense +1".to_string(),
+      StatIncrease::Health  => format!( "Max health +{}", HEALTH_INCREASE ),
+      StatIncrease::Speed   => format!( "Speed +{}", SPEED_INCREASE )
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  
+  fn set_thresholds( thresholds : Vec<u32> ) {
+    LEVELS.with( |levels| {
+      *levels.borrow_mut() = LevelConfig { thresholds: thresholds, health_per_level: 2 }
+    } );
+  }
+  
+  #[test]
+  fn gain_below_threshold() {
+    set_thresholds( vec![ 10, 25, 50 ] );
+    let mut experience = Experience::new();
+    
+    assert_eq!( experience.gain( 9 ), 0 );
+    assert_eq!( experience.level, 1 );
+    assert_eq!( experience.pending, 0 );
+  }
+  
+  #[test]
+  fn gain_reaching_threshold() {
+    set_thresholds( vec![ 10, 25, 50 ] );
+    let mut experience = Experience::new();
+    
+    experience.gain( 9 );
+    assert_eq!( experience.gain( 1 ), 1 );
+    assert_eq!( experience.level, 2 );
+    assert_eq!( experience.pending, 1 );
+  }
+  
+  #[test]
+  fn gain_several_levels_at_once() {
+    set_thresholds( vec![ 10, 25, 50 ] );
+    let mut experience = Experience::new();
+    
+    assert_eq!( experience.gain( 30 ), 2 );
+    assert_eq!( experience.level, 3 );
+    assert_eq!( experience.pending, 2 );
+  }
+  
+  #[test]
+  fn gain_stops_at_the_highest_level() {
+    set_thresholds( vec![ 10, 25, 50 ] );
+    let mut experience = Experience::new();
+    
+    assert_eq!( experience.gain( 1000 ), 3 );
+    assert_eq!( experience.level, 4 );
+    assert_eq!( next_threshold( experience.level ), None );
+    assert_eq!( experience.gain( 1000 ), 0 );
+  }
+}
@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+
+use util::*;
+
+/// The name of the faction the player belongs to.
+pub const PLAYER_FACTION : &'static str = "player";
+
+/// The side an actor is on, as defined in `data/faction.toml`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Faction( usize );
+
+#[derive(RustcDecodable)]
+struct FactionConfig {
+  hostile : Vec<String>
+}
+
+// A faction's name along with the names of the factions it's hostile to
+thread_local!( static FACTIONS : RefCell<Vec<(String, Vec<String>)>> =
+  RefCell::new( Vec::new() ) );
+
+/// Loads the factions and their relations from `data/faction.toml`.
+pub fn load_factions() {
+  use toml::decode;
+  
+  let filename = "data/faction.toml";
+  let data = load_data_file( filename );
+  
+  FACTIONS.with( |factions| {
+    let mut f = factions.borrow_mut();
+    
+    for (entry_name, entry_value) in data.into_iter() {
+      let config : FactionConfig = decode( entry_value )
+        .expect( &format!( "Invalid faction `{}` in '{}'", entry_name, filename ) );
+      
+      f.push( (entry_name, config.hostile) );
+    }
+    
+    for &(ref name, ref hostile) in f.iter() {
+      for other in hostile {
+        assert!( f.iter().any( |&(ref n, _)| n == other )
+               , "The faction `{}` is hostile to the unknown faction `{}`", name, other );
+      }
+    }
+  } );
+}
+
+impl Faction {
+  /// Looks up a faction by its name in the data file.
+  pub fn from_config( name : &str ) -> Option<Faction> {
+    FACTIONS.with( |factions| {
+      factions.borrow().iter().position( |&(ref n, _)| n == name ).map( Faction )
+    } )
+  }
+  
+  /// Whether members of the two factions fight each other. It's enough for
+  /// either of them to be hostile to the other.
+  pub fn is_hostile_to( self, other : Faction ) -> bool {
+    FACTIONS.with( |factions| {
+      let f = factions.borrow();
+      let (ref name, ref hostile) = f[ self.0 ];
+      let (ref other_name, ref other_hostile) = f[ other.0 ];
+      
+      hostile.contains( other_name ) || other_hostile.contains( name )
+    } )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  
+  fn define( factions : &[(&str, &[&str])] ) {
+    FACTIONS.with( |f| {
+      *f.borrow_mut() = factions.iter()
+        .map( |&(name, hostile)| {
+          (name.to_string(), hostile.iter().map( |h| h.to_string() ).collect())
+        } )
+        .collect();
+    } );
+  }
+  
+  fn faction( name : &str ) -> Faction {
+    Faction::from_config( name ).expect( "unknown faction" )
+  }
+  
+  #[test]
+  fn hostility_goes_both_ways() {
+    define( &[ ("player", &[]), ("goblins", &[ "player" ]) ] );
+    
+    assert!( faction( "goblins" ).is_hostile_to( faction( "player" ) ) );
+    assert!( faction( "player" ).is_hostile_to( faction( "goblins" ) ) );
+  }
+  
+  #[test]
+  fn unlisted_factions_are_peaceful() {
+    define( &[ ("player", &[]), ("villagers", &[]), ("goblins", &[ "player" ]) ] );
+    
+    assert!( !faction( "player" ).is_hostile_to( faction( "villagers" ) ) );
+    assert!( !faction( "villagers" ).is_hostile_to( faction( "goblins" ) ) );
+    assert!( !faction( "player" ).is_hostile_to( faction( "player" ) ) );
+  }
+  
+  #[test]
+  fn unknown_factions_are_not_found() {
+    define( &[ ("player", &[]) ] );
+    
+    assert_eq!( Faction::from_config( "dragons" ), None );
+  }
+}
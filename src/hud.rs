@@ -0,0 +1,77 @@
+use tcod::Console;
+use tcod::console::{TextAlignment, BackgroundFlag};
+
+use description::*;
+use world::*;
+use entity::*;
+use hunger::HungerStage;
+use experience::next_threshold;
+use util::ScreenPos;
+
+pub struct Hud {
+  position : ScreenPos
+}
+
+impl Hud {
+  pub fn new( position : ScreenPos ) -> Hud {
+    Hud {
+      position: position
+    }
+  }
+  
+  /// Shows the state of the given entity.
+  pub fn render<C : Console>( &self, ctx : &mut C, world : &World, e : Entity ) {
+    let (x, mut y) = self.position.into();
+    
+    if world.entities.stats.has( e ) {
+      let stats = world.effective_stats( e );
+      
+      ctx.print_ex( x, y, BackgroundFlag::None, TextAlignment::Left
+                  , format!( "HP: {}/{}", stats.health, stats.max_health ) );
+      ctx.print_ex( x, y + 1, BackgroundFlag::None, TextAlignment::Left
+                  , format!( "Atk: {} Def: {}", stats.attack, stats.defense ) );
+    }
+    y += 2;
+    
+    if let Some( experience ) = world.entities.experience.get( e ) {
+      let progress = match next_threshold( experience.level ) {
+        Some( threshold ) => format!( "{}/{}", experience.points, threshold ),
+        None => format!( "{}", experience.points )
+      };
+      
+      ctx.print_ex( x, y, BackgroundFlag::None, TextAlignment::Left
+                  , format!( "Level: {} (XP {})", experience.level, progress ) );
+    }
+    
+    if let Some( mana ) = world.entities.mana.get( e ) {
+      ctx.print_ex( x, y + 1, BackgroundFlag::None, TextAlignment::Left
+                  , format!( "MP: {}/{}", mana.current, mana.max ) );
+    }
+    
+    if let Some( purse ) = world.entities.purses.get( e ) {
+      ctx.print_ex( x, y + 2, BackgroundFlag::None, TextAlignment::Left
+                  , format!( "Gold: {}", purse.gold ) );
+    }
+    y += 3;
+    
+    if let Some( hunger ) = world.entities.hunger.get( e ) {
+      if hunger.stage() != HungerStage::Fed {
+        ctx.print_ex( x, y, BackgroundFlag::None, TextAlignment::Left
+                    , hunger.stage().name() );
+      }
+    }
+    y += 2;
+    
+    let actor = match world.entities.actors.get( e ) {
+      Some( actor ) => actor,
+      None => return
+    };
+    
+    for effect in actor.effects.iter() {
+      ctx.print_ex( x, y, BackgroundFlag::None, TextAlignment::Left
+                  , format!( "{} ({})", effect.kind.description().name()
+                                      , effect.remaining ) );
+      y += 1;
+    }
+  }
+}
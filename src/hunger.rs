@@ -0,0 +1,161 @@
+use self::HungerStage::*;
+
+/// The most food an actor can have in its stomach.
+pub const MAX_FOOD : u32 = 2000;
+
+/// How much food an actor starts out with.
+pub const STARTING_FOOD : u32 = 1500;
+
+/// The amount of time it takes to digest a unit of food, measured in the
+/// same units as `Action::duration`.
+pub const HUNGER_TICK : u32 = 100;
+
+/// How hungry an actor is, from not at all to starving.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum HungerStage {
+  Fed,
+  Hungry,
+  Weak,
+  Fainting,
+  Starving
+}
+
+impl HungerStage {
+  pub fn name( self ) -> &'static str {
+    match self {
+      Fed      => "Fed",
+      Hungry   => "Hungry",
+      Weak     => "Weak",
+      Fainting => "Fainting",
+      Starving => "Starving"
+    }
+  }
+  
+  /// How much longer, in percent, actions take at this stage.
+  pub fn slowdown( self ) -> u32 {
+    match self {
+      Fed | Hungry        => 0,
+      Weak                => 25,
+      Fainting | Starving => 50
+    }
+  }
+  
+  /// Whether wounds can still heal by themselves at this stage.
+  pub fn allows_healing( self ) -> bool {
+    self < Weak
+  }
+}
+
+/// The food an actor has left, which goes down as time passes.
+pub struct Hunger {
+  pub food : u32,
+  // Time passed since the last unit of food was digested
+  elapsed  : u32
+}
+
+impl Hunger {
+  pub fn new( food : u32 ) -> Hunger {
+    Hunger {
+      food: food,
+      elapsed: 0
+    }
+  }
+  
+  pub fn stage( &self ) -> HungerStage {
+    match self.food {
+      0          => Starving,
+      1...49     => Fainting,
+      50...199   => Weak,
+      200...499  => Hungry,
+      _          => Fed
+    }
+  }
+  
+  /// Digests food for `duration`, returning the number of whole ticks that
+  /// passed while the stomach was already empty.
+  pub fn advance( &mut self, duration : u32 ) -> u32 {
+    self.elapsed += duration;
+    
+    let ticks = self.elapsed / HUNGER_TICK;
+    self.elapsed %= HUNGER_TICK;
+    
+    let starved = if ticks > self.food { ticks - self.food } else { 0 };
+    self.food = self.food.saturating_sub( ticks );
+    
+    starved
+  }
+  
+  pub fn eat( &mut self, amount : u32 ) {
+    self.food = ::std::cmp::min( self.food + amount, MAX_FOOD );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  
+  #[test]
+  fn stages_follow_the_food_left() {
+    assert_eq!( Hunger::new( STARTING_FOOD ).stage(), Fed );
+    assert_eq!( Hunger::new( 500 ).stage(), Fed );
+    assert_eq!( Hunger::new( 499 ).stage(), Hungry );
+    assert_eq!( Hunger::new( 199 ).stage(), Weak );
+    assert_eq!( Hunger::new( 49 ).stage(), Fainting );
+    assert_eq!( Hunger::new( 0 ).stage(), Starving );
+  }
+  
+  #[test]
+  fn advance_digests_a_unit_per_tick() {
+    let mut hunger = Hunger::new( 500 );
+    
+    assert_eq!( hunger.advance( HUNGER_TICK / 2 ), 0 );
+    assert_eq!( hunger.food, 500 );
+    assert_eq!( hunger.stage(), Fed );
+    
+    // The half tick left over counts towards the next one
+    assert_eq!( hunger.advance( HUNGER_TICK / 2 ), 0 );
+    assert_eq!( hunger.food, 499 );
+    assert_eq!( hunger.stage(), Hungry );
+  }
+  
+  #[test]
+  fn advance_moves_through_the_stages() {
+    let mut hunger = Hunger::new( 200 );
+    
+    hunger.advance( HUNGER_TICK );
+    assert_eq!( hunger.stage(), Weak );
+    
+    hunger.advance( HUNGER_TICK * 150 );
+    assert_eq!( hunger.stage(), Fainting );
+    
+    hunger.advance( HUNGER_TICK * 49 );
+    assert_eq!( hunger.stage(), Starving );
+  }
+  
+  #[test]
+  fn advance_counts_ticks_spent_starving() {
+    let mut hunger = Hunger::new( 2 );
+    
+    assert_eq!( hunger.advance( HUNGER_TICK * 5 ), 3 );
+    assert_eq!( hunger.food, 0 );
+    assert_eq!( hunger.advance( HUNGER_TICK ), 1 );
+  }
+  
+  #[test]
+  fn eating_fills_up_to_the_maximum() {
+    let mut hunger = Hunger::new( 100 );
+    
+    hunger.eat( 300 );
+    assert_eq!( hunger.food, 400 );
+    
+    hunger.eat( MAX_FOOD );
+    assert_eq!( hunger.food, MAX_FOOD );
+  }
+  
+  #[test]
+  fn healing_stops_once_weak() {
+    assert!( Hungry.allows_healing() );
+    assert!( !Weak.allows_healing() );
+    assert!( !Starving.allows_healing() );
+  }
+}
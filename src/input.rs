@@ -1,52 +1,1283 @@
 use ::tcod::input::{Key, KEY_PRESSED};
+use ::tcod::Console;
+use ::tcod::console::{BackgroundFlag, TextAlignment};
+use ::tcod::colors;
 
 use util::*;
+use actor;
+use pathfind;
+use pathfind::DistanceMap;
+use options::options;
+use targeting::Targeting;
+use equipment::{EquipSlot, EQUIP_SLOTS};
+use ui::{LetterMenu, SelectionList, DialoguePanel};
+use dialogue::{Dialogue, dialogue};
+use experience::{STAT_INCREASES, next_threshold};
+use skill::SKILL_KINDS;
+use spell::{SpellId, SpellTargeting, spell};
+use quest::{QuestStatus, quest};
+use description::*;
+use map::Interaction;
+use entity::Entity;
+use event::Event;
+use world::World;
 use Game;
 
 enum InputState {
-  Toplevel
+  Toplevel,
+  ChooseDirection( Interaction ),
+  /// Resting until healed, remembering the health the player had when the
+  /// last rest began
+  Resting( u32 ),
+  /// Moving in a direction until something interesting happens
+  Running( RunState ),
+  /// Picking the position to travel to with a cursor
+  ChooseTravelTarget( Position ),
+  /// Following `Input::travel_path`, remembering the number of messages
+  /// logged when the travel began
+  Travelling( usize ),
+  /// Heading for the closest unexplored tile, remembering the health the
+  /// player had at the last step
+  Exploring( u32 ),
+  /// Aiming with `Input::targeting`
+  Targeting( TargetPurpose ),
+  /// Looking at the equipment screen
+  Equipment,
+  /// Picking the item to put into a slot
+  ChooseEquipment( EquipSlot ),
+  /// Picking the consumable item to use
+  ChooseItemToUse,
+  /// Picking the spell to cast from the spell list
+  ChooseSpell,
+  /// Picking a stat to increase with `Input::stat_choice` after levelling up
+  ChooseStatIncrease,
+  /// Looking at the character sheet
+  CharacterSheet,
+  /// Looking at the quest journal
+  Journal,
+  /// Trading with the given shopkeeper
+  Shop( Entity, ShopMode ),
+  /// Having `Input::conversation`
+  Talking
+}
+
+/// Which side of a trade the shop screen shows.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ShopMode {
+  Buy,
+  Sell
+}
+
+/// What the player is aiming for.
+#[derive(Clone, Copy)]
+enum TargetPurpose {
+  /// Shooting or throwing a ranged weapon
+  Fire( Entity ),
+  /// Casting a spell
+  Cast( SpellId )
+}
+
+// Where the player is in a conversation
+struct Conversation {
+  speaker  : Entity,
+  dialogue : Dialogue,
+  node     : String,
+  // The indices of the choices of the node the player can pick from, in the
+  // order they're listed
+  choices  : Vec<usize>,
+  panel    : DialoguePanel
+}
+
+#[derive(Clone, Copy)]
+struct RunState {
+  direction       : Direction,
+  // The number of open tiles around the player at the last step
+  open_neighbours : usize,
+  // The number of messages logged when the run began
+  messages        : usize
 }
 
 pub struct Input {
-  state      : InputState,
-  key_config : (), // Placeholder
+  state       : InputState,
+  travel_path : Vec<Position>,
+  targeting   : Option<Targeting>,
+  stat_choice : Option<SelectionList>,
+  conversation : Option<Conversation>,
+  // The page shown of the open letter menu
+  menu_page   : usize,
+  key_config  : (), // Placeholder
 }
 
 impl Input {
   pub fn new() -> Input {
     Input {
       state: InputState::Toplevel,
+      travel_path: Vec::new(),
+      targeting: None,
+      stat_choice: None,
+      conversation: None,
+      menu_page: 0,
       key_config: ()
     }
   }
   
+  /// Draws anything the current input state needs to show, like cursors.
+  pub fn render<C : Console>( &self, ctx : &mut C, world : &World ) {
+    match self.state {
+      InputState::ChooseTravelTarget( pos ) =>
+        ctx.set_char_background( pos.x as i32, pos.y as i32
+                               , colors::YELLOW, BackgroundFlag::Set ),
+      InputState::Targeting( _ ) =>
+        if let Some( ref targeting ) = self.targeting {
+          targeting.render( ctx, world );
+        },
+      InputState::Equipment =>
+        Input::equipment_menu( world ).on_page( self.menu_page ).render( ctx ),
+      InputState::ChooseEquipment( slot ) => {
+        let candidates = Input::equipment_candidates( world, slot );
+        Input::candidates_menu( world, slot, &candidates ).on_page( self.menu_page ).render( ctx );
+      },
+      InputState::ChooseItemToUse => {
+        let usable = Input::usable_items( world );
+        Input::usable_items_menu( world, &usable ).on_page( self.menu_page ).render( ctx );
+      },
+      InputState::ChooseSpell =>
+        Input::spell_menu( world ).on_page( self.menu_page ).render( ctx ),
+      InputState::ChooseStatIncrease =>
+        if let Some( ref list ) = self.stat_choice {
+          ctx.print_frame( 24, 10, 32, STAT_INCREASES.len() as i32 + 5, true
+                         , BackgroundFlag::Set, Some( "Level up!" ) );
+          ctx.print_ex( 40, 11, BackgroundFlag::None, TextAlignment::Center
+                      , "Choose a stat to increase" );
+          list.render( ctx );
+        },
+      InputState::CharacterSheet =>
+        Input::render_character_sheet( ctx, world ),
+      InputState::Journal =>
+        Input::render_journal( ctx, world ),
+      InputState::Shop( keeper, mode ) => {
+        let items = Input::shop_items( world, keeper, mode );
+        Input::shop_menu( world, mode, &items ).on_page( self.menu_page ).render( ctx );
+      },
+      InputState::Talking =>
+        if let Some( ref conversation ) = self.conversation {
+          conversation.panel.render( ctx );
+        },
+      _ => {}
+    }
+  }
+  
   pub fn update( &mut self, game : &Game ) {
     if let Some( new_state ) = match self.state {
-      InputState::Toplevel => self.update_toplevel( game )
+      InputState::Toplevel => self.update_toplevel( game ),
+      InputState::ChooseDirection( how ) =>
+        self.update_choose_direction( game, how ),
+      InputState::Resting( last_health ) =>
+        self.update_resting( game, last_health ),
+      InputState::Running( run ) =>
+        self.update_running( game, run ),
+      InputState::ChooseTravelTarget( pos ) =>
+        self.update_choose_travel_target( game, pos ),
+      InputState::Travelling( messages ) =>
+        self.update_travelling( game, messages ),
+      InputState::Exploring( last_health ) =>
+        self.update_exploring( game, last_health ),
+      InputState::Targeting( purpose ) =>
+        self.update_targeting( game, purpose ),
+      InputState::Equipment =>
+        self.update_equipment( game ),
+      InputState::ChooseEquipment( slot ) =>
+        self.update_choose_equipment( game, slot ),
+      InputState::ChooseItemToUse =>
+        self.update_choose_item_to_use( game ),
+      InputState::ChooseSpell =>
+        self.update_choose_spell( game ),
+      InputState::ChooseStatIncrease =>
+        self.update_choose_stat_increase( game ),
+      InputState::CharacterSheet =>
+        self.update_info_screen( game ),
+      InputState::Journal =>
+        self.update_info_screen( game ),
+      InputState::Shop( keeper, mode ) =>
+        self.update_shop( game, keeper, mode ),
+      InputState::Talking =>
+        self.update_talking( game )
     } {
       self.state = new_state;
+      self.menu_page = 0;
     }
   }
   
   fn update_toplevel( &mut self, game : &Game ) -> Option<InputState> {
+    use ::tcod::input::KeyCode::{Char, NumPad5};
+    
+    // Stat increases earned by levelling up are picked before anything else
+    let pending = {
+      let world = game.world.borrow();
+      
+      world.entities.experience.get( world.player() )
+        .map( |experience| experience.pending )
+        .unwrap_or( 0 )
+    };
+    
+    if pending > 0 {
+      let choices = STAT_INCREASES.iter().map( |increase| increase.name() ).collect();
+      
+      self.stat_choice = Some( SelectionList::new( choices, (40, 13).into()
+                                                 , true, TextAlignment::Center ) );
+      
+      return Some( InputState::ChooseStatIncrease )
+    }
+    
     while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      // Holding shift while moving starts running
+      match Input::directional_key( key ) {
+        Some( direction ) if key.shift => {
+          let run = {
+            let world = game.world.borrow();
+            let player = world.player();
+            let pos = world.entities.position( player ).expect( "player has no position" );
+            
+            RunState {
+              direction: direction,
+              open_neighbours: Input::open_neighbours( &world, pos ),
+              messages: game.message_log.borrow().added_count()
+            }
+          };
+          
+          return Some( InputState::Running( run ) )
+        },
+        _ => {}
+      }
+      
       // Check for movements
       if let Some( direction ) = Input::directional_key( key ) {
         let world = game.world.borrow();
-        let mut player = world.player.borrow_mut();
+        let player = world.player();
         
-        let maybe_reason = player.actor.move_direction( direction, &world );
+        // Bumping into a shopkeeper opens their shop
+        if let Some( keeper ) = Input::shopkeeper_towards( &world, direction ) {
+          return Some( InputState::Shop( keeper, ShopMode::Buy ) )
+        }
         
-        if let Some( reason ) = maybe_reason {
+        // Bumping into someone peaceful to talk to starts a conversation
+        if let Some( speaker ) = Input::speaker_towards( &world, direction ) {
+          let name = world.entities.speakers.get( speaker )
+            .map( |s| s.dialogue.clone() )
+            .expect( "speaker has no dialogue" );
+          let dialogue = dialogue( &name ).expect( &format!( "No dialogue `{}` exists", name ) );
+          let start = dialogue.start.clone();
+          
+          self.conversation = Some( Input::conversation_at( &world, speaker, dialogue, start ) );
+          
+          return Some( InputState::Talking )
+        }
+        
+        // Failures are reported to the log through the world's events
+        actor::move_direction( &world, player, direction );
+        
+        continue;
+      }
+      
+      if key.code == Char && key.printable == 'g' {
+        let world = game.world.borrow();
+        let player = world.player();
+        
+        actor::pick_up( &world, player );
+        
+        continue;
+      }
+      
+      if key.code == NumPad5 || ( key.code == Char && key.printable == '.' ) {
+        let world = game.world.borrow();
+        let player = world.player();
+        
+        actor::wait( &world, player );
+        
+        continue;
+      }
+      
+      if key.code == Char && key.printable == 's' {
+        let world = game.world.borrow();
+        let player = world.player();
+        
+        actor::search( &world, player );
+        
+        continue;
+      }
+      
+      if key.code == Char && key.printable == 'R' {
+        let world = game.world.borrow();
+        let player = world.player();
+        let health = world.entities.stats.get( player ).expect( "player has no stats" ).health;
+        
+        game.message_log.borrow_mut().add_message( "You start resting.".to_string() );
+        
+        return Some( InputState::Resting( health ) )
+      }
+      
+      if key.code == Char && key.printable == 'C' {
+        return Some( InputState::CharacterSheet )
+      }
+      
+      if key.code == Char && key.printable == 'J' {
+        return Some( InputState::Journal )
+      }
+      
+      if key.code == Char && key.printable == 'e' {
+        return Some( InputState::Equipment )
+      }
+      
+      if key.code == Char && key.printable == 'u' {
+        if Input::usable_items( &game.world.borrow() ).is_empty() {
           game.message_log.borrow_mut().add_message(
-            format!( "{}", reason ) );
+            "You have nothing to use.".to_string() );
+          
+          continue;
+        }
+        
+        return Some( InputState::ChooseItemToUse )
+      }
+      
+      if key.code == Char && key.printable == 'z' {
+        if Input::known_spells( &game.world.borrow() ).is_empty() {
+          game.message_log.borrow_mut().add_message(
+            "You don't know any spells.".to_string() );
+          
+          continue;
+        }
+        
+        return Some( InputState::ChooseSpell )
+      }
+      
+      if key.code == Char && key.printable == 'x' {
+        let world = game.world.borrow();
+        let health = world.entities.stats.get( world.player() )
+          .expect( "player has no stats" ).health;
+        
+        return Some( InputState::Exploring( health ) )
+      }
+      
+      if key.code == Char && ( key.printable == 'f' || key.printable == 't' ) {
+        let thrown = key.printable == 't';
+        let world = game.world.borrow();
+        let player = world.player();
+        
+        match actor::ranged_weapon( &world, player, thrown ) {
+          Some( weapon ) => {
+            let pos = world.entities.position( player ).expect( "player has no position" );
+            let range = world.entities.ranged_weapons.get( weapon )
+              .expect( "weapon is not ranged" ).range;
+            
+            self.targeting = Some( Targeting::new( &world, pos, range ) );
+            
+            return Some( InputState::Targeting( TargetPurpose::Fire( weapon ) ) )
+          },
+          None => {
+            let reason = if thrown {
+              actor::ActionFailureReason::NoThrowingWeapon
+            } else {
+              actor::ActionFailureReason::NoRangedWeapon
+            };
+            
+            world.emit( Event::ActionFailed { entity: player, reason: reason } );
+          }
+        }
+        
+        continue;
+      }
+      
+      if key.code == Char && key.printable == '_' {
+        let world = game.world.borrow();
+        let pos = world.entities.position( world.player() ).expect( "player has no position" );
+        
+        game.message_log.borrow_mut().add_message(
+          "Where do you want to go?".to_string() );
+        
+        return Some( InputState::ChooseTravelTarget( pos ) )
+      }
+      
+      if let Some( how ) = Input::interaction_key( key ) {
+        let prompt = match how {
+          Interaction::Open => "Open in which direction?",
+          Interaction::Close => "Close in which direction?"
+        };
+        
+        game.message_log.borrow_mut().add_message( prompt.to_string() );
+        
+        return Some( InputState::ChooseDirection( how ) )
+      }
+    }
+    
+    None
+  }
+  
+  fn update_choose_direction( &mut self, game : &Game, how : Interaction )
+    -> Option<InputState> {
+    use ::tcod::input::KeyCode::Escape;
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      if key.code == Escape {
+        return Some( InputState::Toplevel )
+      }
+      
+      if let Some( direction ) = Input::directional_key( key ) {
+        let world = game.world.borrow();
+        let player = world.player();
+        
+        actor::interact( &world, player, direction, how );
+        
+        return Some( InputState::Toplevel )
+      }
+    }
+    
+    None
+  }
+  
+  // Keeps resting one turn at a time until the player is fully healed, or
+  // something interrupts it
+  fn update_resting( &mut self, game : &Game, last_health : u32 )
+    -> Option<InputState> {
+    
+    let interruption = {
+      let world = game.world.borrow();
+      let player = world.player();
+      let stats = world.entities.stats.get( player ).expect( "player has no stats" );
+      
+      if game.root.check_for_keypress( KEY_PRESSED ).is_some() {
+        Some( "You stop resting." )
+      } else if world.hostile_in_view() {
+        Some( "You stop resting, there's an enemy nearby!" )
+      } else if stats.health < last_health {
+        Some( "You stop resting, something is hurting you!" )
+      } else if stats.health >= stats.max_health {
+        Some( "You feel fully rested." )
+      } else if !world.can_heal( player ) {
+        Some( "You're too hungry to rest, your wounds won't heal." )
+      } else {
+        None
+      }
+    };
+    
+    if let Some( msg ) = interruption {
+      game.message_log.borrow_mut().add_message( msg.to_string() );
+      
+      return Some( InputState::Toplevel )
+    }
+    
+    let world = game.world.borrow();
+    let player = world.player();
+    let health = world.entities.stats.get( player ).expect( "player has no stats" ).health;
+    
+    actor::rest( &world, player );
+    
+    Some( InputState::Resting( health ) )
+  }
+  
+  // The number of tiles next to `pos` that can be walked onto
+  fn open_neighbours( world : &World, pos : Position ) -> usize {
+    pathfind::neighbours( &world.map, pos ).into_iter()
+      .filter( |&p| !world.map.tile_at( p ).is_solid() )
+      .count()
+  }
+  
+  // Whether the player moving on their own should stop before the next
+  // step: because a key was pressed, an enemy came into view, something was
+  // logged since `messages` messages had been logged or there's something
+  // lying at the player's feet.
+  fn movement_interrupted( game : &Game, messages : usize ) -> bool {
+    if game.root.check_for_keypress( KEY_PRESSED ).is_some() {
+      return true
+    }
+    
+    if game.message_log.borrow().added_count() > messages {
+      return true
+    }
+    
+    let world = game.world.borrow();
+    let pos = world.entities.position( world.player() ).expect( "player has no position" );
+    
+    world.hostile_in_view() || !world.items_at( pos ).is_empty()
+  }
+  
+  fn update_running( &mut self, game : &Game, run : RunState ) -> Option<InputState> {
+    if Input::movement_interrupted( game, run.messages ) {
+      return Some( InputState::Toplevel )
+    }
+    
+    let world = game.world.borrow();
+    let player = world.player();
+    let pos = world.entities.position( player ).expect( "player has no position" );
+    
+    // Stop at doors and wherever the surroundings open up or narrow down,
+    // like at corridor branches
+    let near_door = pathfind::neighbours( &world.map, pos ).into_iter()
+      .any( |p| world.map.tile_at( p ).is_door() );
+    let open_neighbours = Input::open_neighbours( &world, pos );
+    
+    if near_door || open_neighbours != run.open_neighbours {
+      return Some( InputState::Toplevel )
+    }
+    
+    if actor::move_direction( &world, player, run.direction ).is_some() {
+      return Some( InputState::Toplevel )
+    }
+    
+    None
+  }
+  
+  fn update_choose_travel_target( &mut self, game : &Game, pos : Position )
+    -> Option<InputState> {
+    use ::tcod::input::KeyCode::{Enter, Escape};
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      if key.code == Escape {
+        return Some( InputState::Toplevel )
+      }
+      
+      if let Some( direction ) = Input::directional_key( key ) {
+        let world = game.world.borrow();
+        
+        if let Some( next ) = direction.try_offset_position( pos, &world.map ) {
+          return Some( InputState::ChooseTravelTarget( next ) )
+        }
+      }
+      
+      if key.code == Enter {
+        let world = game.world.borrow();
+        let from = world.entities.position( world.player() ).expect( "player has no position" );
+        
+        let path = pathfind::find_path( &world.map, from, pos, |p| {
+          world.path_cost( world.player(), p )
+        } );
+        
+        match path {
+          Some( path ) => {
+            self.travel_path = path;
+            
+            return Some( InputState::Travelling( game.message_log.borrow().added_count() ) )
+          },
+          None => {
+            game.message_log.borrow_mut().add_message(
+              "You can't find a way there.".to_string() );
+            
+            return Some( InputState::Toplevel )
+          }
+        }
+      }
+    }
+    
+    None
+  }
+  
+  // Takes the next step along the travel path
+  fn update_travelling( &mut self, game : &Game, messages : usize ) -> Option<InputState> {
+    if Input::movement_interrupted( game, messages ) {
+      return Some( InputState::Toplevel )
+    }
+    
+    let world = game.world.borrow();
+    let player = world.player();
+    let pos = world.entities.position( player ).expect( "player has no position" );
+    
+    // Steps are only dropped once they've been reached, as a step might have
+    // been spent opening a door instead
+    while self.travel_path.first() == Some( &pos ) {
+      self.travel_path.remove( 0 );
+    }
+    
+    let next = match self.travel_path.first() {
+      Some( &next ) if pos.distance( next ) == 1 => next,
+      _ => return Some( InputState::Toplevel )
+    };
+    
+    let direction = Direction::towards( pos, next ).expect( "the next step is the same position" );
+    
+    if actor::move_direction( &world, player, direction ).is_some() {
+      return Some( InputState::Toplevel )
+    }
+    
+    None
+  }
+  
+  // Takes a step towards the closest unexplored tile the player can reach
+  fn update_exploring( &mut self, game : &Game, last_health : u32 ) -> Option<InputState> {
+    let world = game.world.borrow();
+    let player = world.player();
+    let pos = world.entities.position( player ).expect( "player has no position" );
+    let health = world.entities.stats.get( player ).expect( "player has no stats" ).health;
+    
+    let interruption = if game.root.check_for_keypress( KEY_PRESSED ).is_some() {
+      Some( "You stop exploring." )
+    } else if world.hostile_in_view() {
+      Some( "You stop exploring, there's an enemy nearby!" )
+    } else if health < last_health {
+      Some( "You stop exploring, something is hurting you!" )
+    } else {
+      None
+    };
+    
+    if let Some( msg ) = interruption {
+      game.message_log.borrow_mut().add_message( msg.to_string() );
+      
+      return Some( InputState::Toplevel )
+    }
+    
+    if options().auto_pickup {
+      // Goods in shops are left for the player to buy
+      let free_item = world.items_at( pos ).into_iter()
+        .find( |&item| !world.entities.for_sale.has( item ) );
+      
+      if let Some( item ) = free_item {
+        if actor::pick_up_item( &world, player, item ).is_some() {
+          return Some( InputState::Toplevel )
+        }
+        
+        return Some( InputState::Exploring( health ) )
+      }
+    }
+    
+    // The frontier is every unexplored tile next to an explored one the
+    // player could walk through
+    let map = &world.map;
+    let cost = |p : Position| if map.is_explored( p ) { world.path_cost( player, p ) } else { None };
+    let known = |p : Position| cost( p ).is_some();
+    let mut frontier = Vec::new();
+    
+    for y in 0..map.height {
+      for x in 0..map.width {
+        let p = Position::new( x as u32, y as u32 );
+        
+        if !map.is_explored( p )
+           && pathfind::neighbours( map, p ).into_iter().any( |n| known( n ) ) {
+          frontier.push( p );
+        }
+      }
+    }
+    
+    let distances = DistanceMap::new( map, &frontier, &cost );
+    
+    let next = match distances.next_step( map, pos ) {
+      Some( next ) => next,
+      None => {
+        game.message_log.borrow_mut().add_message(
+          "There's nothing left to explore here.".to_string() );
+        
+        return Some( InputState::Toplevel )
+      }
+    };
+    
+    let direction = Direction::towards( pos, next ).expect( "the next step is the same position" );
+    
+    if actor::move_direction( &world, player, direction ).is_some() {
+      return Some( InputState::Toplevel )
+    }
+    
+    Some( InputState::Exploring( health ) )
+  }
+  
+  fn update_targeting( &mut self, game : &Game, purpose : TargetPurpose )
+    -> Option<InputState> {
+    use ::tcod::input::KeyCode::{Enter, Escape, Tab};
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      let world = game.world.borrow();
+      let mut targeting = self.targeting.take().expect( "no targeting cursor" );
+      
+      if key.code == Escape {
+        return Some( InputState::Toplevel )
+      }
+      
+      if key.code == Enter {
+        let player = world.player();
+        
+        match purpose {
+          TargetPurpose::Fire( weapon ) =>
+            actor::fire( &world, player, weapon, targeting.cursor ),
+          TargetPurpose::Cast( id ) => {
+            actor::cast( &world, player, id, targeting.cursor );
+          }
+        }
+        
+        return Some( InputState::Toplevel )
+      }
+      
+      if key.code == Tab {
+        targeting.cycle( &world );
+      } else if let Some( direction ) = Input::directional_key( key ) {
+        targeting.move_cursor( &world, direction );
+      }
+      
+      self.targeting = Some( targeting );
+    }
+    
+    None
+  }
+  
+  // Lists every equipment slot of the player along with what's in it
+  fn equipment_menu( world : &World ) -> LetterMenu {
+    let equipment = world.entities.equipment.get( world.player() )
+      .expect( "player has no equipment" );
+    
+    let entries = EQUIP_SLOTS.iter()
+      .map( |&slot| {
+        let item = match equipment.get( slot ) {
+          Some( item ) => world.name_of( item ),
+          None => "-".to_string()
+        };
+        
+        format!( "{:<10} {}", slot.name(), item )
+      } )
+      .collect();
+    
+    LetterMenu::new( "Equipment".to_string(), entries, (2, 2).into() )
+  }
+  
+  // The items in the player's inventory that fit into the slot
+  fn equipment_candidates( world : &World, slot : EquipSlot ) -> Vec<Entity> {
+    let inventory = world.entities.inventories.get( world.player() )
+      .expect( "player has no inventory" );
+    
+    inventory.items.iter().cloned()
+      .filter( |&item| {
+        world.entities.equippables.get( item )
+          .map( |equip| equip.slots.contains( &slot ) )
+          .unwrap_or( false )
+      } )
+      .collect()
+  }
+  
+  fn candidates_menu( world : &World, slot : EquipSlot, candidates : &[Entity] )
+    -> LetterMenu {
+    
+    let entries = candidates.iter()
+      .map( |&item| world.name_of( item ) )
+      .collect();
+    
+    LetterMenu::new( slot.name().to_string(), entries, (4, 4).into() )
+  }
+  
+  fn update_equipment( &mut self, game : &Game ) -> Option<InputState> {
+    use ::tcod::input::KeyCode::Escape;
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      if key.code == Escape {
+        return Some( InputState::Toplevel )
+      }
+      
+      let world = game.world.borrow();
+      let player = world.player();
+      
+      let menu = Input::equipment_menu( &world ).on_page( self.menu_page );
+      
+      if let Some( page ) = menu.turn_page( key ) {
+        self.menu_page = page;
+        continue;
+      }
+      
+      let slot = match menu.selection( key ) {
+        Some( idx ) => EQUIP_SLOTS[ idx ],
+        None => continue
+      };
+      
+      let equipped = world.entities.equipment.get( player )
+        .and_then( |equipment| equipment.get( slot ) )
+        .is_some();
+      
+      // Picking a slot with something in it takes it off
+      if equipped {
+        actor::unequip( &world, player, slot );
+        
+        return Some( InputState::Toplevel )
+      }
+      
+      if Input::equipment_candidates( &world, slot ).is_empty() {
+        game.message_log.borrow_mut().add_message(
+          format!( "You have nothing to put there ({}).", slot.name().to_lowercase() ) );
+        
+        continue;
+      }
+      
+      return Some( InputState::ChooseEquipment( slot ) )
+    }
+    
+    None
+  }
+  
+  fn update_choose_equipment( &mut self, game : &Game, slot : EquipSlot )
+    -> Option<InputState> {
+    use ::tcod::input::KeyCode::Escape;
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      if key.code == Escape {
+        return Some( InputState::Equipment )
+      }
+      
+      let world = game.world.borrow();
+      let candidates = Input::equipment_candidates( &world, slot );
+      
+      let menu = Input::candidates_menu( &world, slot, &candidates ).on_page( self.menu_page );
+      
+      if let Some( page ) = menu.turn_page( key ) {
+        self.menu_page = page;
+        continue;
+      }
+      
+      if let Some( idx ) = menu.selection( key ) {
+        actor::equip( &world, world.player(), candidates[ idx ], slot );
+        
+        return Some( InputState::Toplevel )
+      }
+    }
+    
+    None
+  }
+  
+  // The consumable items in the player's inventory
+  fn usable_items( world : &World ) -> Vec<Entity> {
+    let inventory = world.entities.inventories.get( world.player() )
+      .expect( "player has no inventory" );
+    
+    inventory.items.iter().cloned()
+      .filter( |&item| world.entities.consumables.has( item ) )
+      .collect()
+  }
+  
+  fn usable_items_menu( world : &World, items : &[Entity] ) -> LetterMenu {
+    let entries = items.iter().map( |&item| world.name_of( item ) ).collect();
+    
+    LetterMenu::new( "Use which item?".to_string(), entries, (2, 2).into() )
+  }
+  
+  fn update_choose_item_to_use( &mut self, game : &Game ) -> Option<InputState> {
+    use ::tcod::input::KeyCode::Escape;
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      if key.code == Escape {
+        return Some( InputState::Toplevel )
+      }
+      
+      let world = game.world.borrow();
+      let items = Input::usable_items( &world );
+      
+      let menu = Input::usable_items_menu( &world, &items ).on_page( self.menu_page );
+      
+      if let Some( page ) = menu.turn_page( key ) {
+        self.menu_page = page;
+        continue;
+      }
+      
+      if let Some( idx ) = menu.selection( key ) {
+        actor::use_item( &world, world.player(), items[ idx ] );
+        
+        return Some( InputState::Toplevel )
+      }
+    }
+    
+    None
+  }
+  
+  // The spells the player knows
+  fn known_spells( world : &World ) -> Vec<SpellId> {
+    world.entities.spellbooks.get( world.player() )
+      .map( |book| book.spells.clone() )
+      .unwrap_or( Vec::new() )
+  }
+  
+  // Lists the spells the player knows along with what they cost
+  fn spell_menu( world : &World ) -> LetterMenu {
+    let entries = Input::known_spells( world ).into_iter()
+      .map( |id| {
+        let spell = spell( id );
+        
+        format!( "{:<12} {:>2} mana", spell.description().name(), spell.cost )
+      } )
+      .collect();
+    
+    LetterMenu::new( "Cast which spell?".to_string(), entries, (2, 2).into() )
+  }
+  
+  fn update_choose_spell( &mut self, game : &Game ) -> Option<InputState> {
+    use ::tcod::input::KeyCode::Escape;
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      if key.code == Escape {
+        return Some( InputState::Toplevel )
+      }
+      
+      let world = game.world.borrow();
+      let player = world.player();
+      let spells = Input::known_spells( &world );
+      
+      let menu = Input::spell_menu( &world ).on_page( self.menu_page );
+      
+      if let Some( page ) = menu.turn_page( key ) {
+        self.menu_page = page;
+        continue;
+      }
+      
+      let id = match menu.selection( key ) {
+        Some( idx ) => spells[ idx ],
+        None => continue
+      };
+      
+      let pos = world.entities.position( player ).expect( "player has no position" );
+      let spell = spell( id );
+      
+      // Spells cast on oneself don't need aiming
+      if spell.targeting == SpellTargeting::OnSelf {
+        actor::cast( &world, player, id, pos );
+        
+        return Some( InputState::Toplevel )
+      }
+      
+      self.targeting = Some( Targeting::new( &world, pos, spell.range ) );
+      
+      return Some( InputState::Targeting( TargetPurpose::Cast( id ) ) )
+    }
+    
+    None
+  }
+  
+  // The friendly shopkeeper next to the player in the given direction, if any
+  fn shopkeeper_towards( world : &World, direction : Direction ) -> Option<Entity> {
+    let player = world.player();
+    let pos = world.entities.position( player ).expect( "player has no position" );
+    
+    direction.try_offset_position( pos, &world.map )
+      .and_then( |p| world.actor_at( p ) )
+      .and_then( |other| {
+        if world.entities.shopkeepers.has( other ) && !world.are_hostile( player, other ) {
+          Some( other )
+        } else {
+          None
+        }
+      } )
+  }
+  
+  // The items the player can buy from the shopkeeper, or sell to them
+  fn shop_items( world : &World, keeper : Entity, mode : ShopMode ) -> Vec<Entity> {
+    match mode {
+      ShopMode::Buy => world.entities.for_sale.iter()
+        .filter( |&(_, for_sale)| for_sale.borrow().keeper == keeper )
+        .map( |(item, _)| item )
+        .collect(),
+      ShopMode::Sell => {
+        let inventory = world.entities.inventories.get( world.player() )
+          .expect( "player has no inventory" );
+        
+        inventory.items.iter().cloned()
+          .filter( |&item| world.entities.prices.has( item ) && !world.entities.for_sale.has( item ) )
+          .collect()
+      }
+    }
+  }
+  
+  fn shop_menu( world : &World, mode : ShopMode, items : &[Entity] ) -> LetterMenu {
+    let gold = world.entities.purses.get( world.player() ).map( |p| p.gold ).unwrap_or( 0 );
+    
+    let entries = items.iter()
+      .map( |&item| {
+        let price = world.entities.prices.get( item ).map( |p| *p ).expect( "item has no price" );
+        let price = if mode == ShopMode::Buy { price.0 } else { price.selling() };
+        
+        format!( "{:<28} {:>4} gold", world.name_of( item ), price )
+      } )
+      .collect();
+    
+    let title = match mode {
+      ShopMode::Buy => format!( "Buy ({} gold, Tab to sell)", gold ),
+      ShopMode::Sell => format!( "Sell ({} gold, Tab to buy)", gold )
+    };
+    
+    LetterMenu::new( title, entries, (2, 2).into() )
+  }
+  
+  fn update_shop( &mut self, game : &Game, keeper : Entity, mode : ShopMode )
+    -> Option<InputState> {
+    use ::tcod::input::KeyCode::{Escape, Tab};
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      if key.code == Escape {
+        return Some( InputState::Toplevel )
+      }
+      
+      if key.code == Tab {
+        let other = if mode == ShopMode::Buy { ShopMode::Sell } else { ShopMode::Buy };
+        
+        return Some( InputState::Shop( keeper, other ) )
+      }
+      
+      let world = game.world.borrow();
+      let player = world.player();
+      let items = Input::shop_items( &world, keeper, mode );
+      
+      let menu = Input::shop_menu( &world, mode, &items ).on_page( self.menu_page );
+      
+      if let Some( page ) = menu.turn_page( key ) {
+        self.menu_page = page;
+        continue;
+      }
+      
+      if let Some( idx ) = menu.selection( key ) {
+        match mode {
+          ShopMode::Buy => actor::buy( &world, player, items[ idx ] ),
+          ShopMode::Sell => actor::sell( &world, player, items[ idx ], keeper )
+        };
+        
+        return None
+      }
+    }
+    
+    None
+  }
+  
+  // The peaceful actor to talk to next to the player in the given direction,
+  // if any
+  fn speaker_towards( world : &World, direction : Direction ) -> Option<Entity> {
+    let player = world.player();
+    let pos = world.entities.position( player ).expect( "player has no position" );
+    
+    direction.try_offset_position( pos, &world.map )
+      .and_then( |p| world.actor_at( p ) )
+      .and_then( |other| {
+        if world.entities.speakers.has( other ) && !world.are_hostile( player, other ) {
+          Some( other )
+        } else {
+          None
         }
+      } )
+  }
+  
+  // Goes to the given node of the dialogue, offering the choices whose
+  // conditions hold
+  fn conversation_at( world : &World, speaker : Entity, dialogue : Dialogue, node : String )
+    -> Conversation {
+    
+    let (choices, panel) = {
+      let current = dialogue.node( &node );
+      let player = world.player();
+      
+      let choices : Vec<usize> = current.choices.iter().enumerate()
+        .filter( |&(_, choice)| {
+          choice.condition.as_ref().map( |c| world.meets_condition( player, c ) ).unwrap_or( true )
+        } )
+        .map( |(i, _)| i )
+        .collect();
+      
+      let mut texts : Vec<String> = choices.iter()
+        .map( |&i| current.choices[ i ].text.clone() )
+        .collect();
+      
+      // There's always a way out of a conversation
+      if texts.is_empty() {
+        texts.push( "(Leave)".to_string() );
+      }
+      
+      let name = capitalize( world.kind_of( speaker ).description().name() );
+      
+      (choices, DialoguePanel::new( name, current.text.clone(), texts, (10, 8).into(), 60 ))
+    };
+    
+    Conversation {
+      speaker:  speaker,
+      dialogue: dialogue,
+      node:     node,
+      choices:  choices,
+      panel:    panel
+    }
+  }
+  
+  fn update_talking( &mut self, game : &Game ) -> Option<InputState> {
+    use ::tcod::input::KeyCode::Escape;
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      if key.code == Escape {
+        self.conversation = None;
+        
+        return Some( InputState::Toplevel )
+      }
+      
+      let mut conversation = self.conversation.take().expect( "no conversation is going on" );
+      
+      let idx = match conversation.panel.handle_key( key ) {
+        Some( idx ) => idx,
+        None => {
+          self.conversation = Some( conversation );
+          continue;
+        }
+      };
+      
+      let choice = match conversation.choices.get( idx ) {
+        Some( &i ) => conversation.dialogue.node( &conversation.node ).choices[ i ].clone(),
+        None => return Some( InputState::Toplevel )
+      };
+      
+      let speaker = conversation.speaker;
+      
+      {
+        let mut world = game.world.borrow_mut();
+        let player = world.player();
+        
+        for effect in &choice.effects {
+          world.apply_dialogue_effect( player, speaker, effect );
+        }
+        
+        if world.are_hostile( player, speaker ) {
+          return Some( InputState::Toplevel )
+        }
+      }
+      
+      return match choice.next {
+        Some( next ) => {
+          let world = game.world.borrow();
+          
+          self.conversation = Some( Input::conversation_at( &world, speaker, conversation.dialogue
+                                                          , next ) );
+          
+          None
+        },
+        None => Some( InputState::Toplevel )
       }
     }
     
     None
   }
   
+  fn update_choose_stat_increase( &mut self, game : &Game ) -> Option<InputState> {
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      let idx = match self.stat_choice.as_mut().and_then( |list| list.handle_key( key ) ) {
+        Some( idx ) => idx,
+        None => continue
+      };
+      
+      let increase = STAT_INCREASES[ idx ];
+      let mut world = game.world.borrow_mut();
+      let player = world.player();
+      
+      world.increase_stat( player, increase );
+      
+      game.message_log.borrow_mut().add_message(
+        format!( "You feel stronger ({}).", increase.name() ) );
+      
+      self.stat_choice = None;
+      
+      return Some( InputState::Toplevel )
+    }
+    
+    None
+  }
+  
+  // Shows who the player is, their stats and their skills
+  fn render_character_sheet<C : Console>( ctx : &mut C, world : &World ) {
+    let player = world.player();
+    let stats = world.effective_stats( player );
+    
+    let mut lines = vec![ world.character.title()
+                        , format!( "{} background", world.character.background.description().name() )
+                        , String::new()
+                        , format!( "Health  {}/{}", stats.health, stats.max_health )
+                        , format!( "Attack  {}", stats.attack )
+                        , format!( "Defense {}", stats.defense )
+                        , format!( "Speed   {}", stats.speed ) ];
+    
+    if let Some( experience ) = world.entities.experience.get( player ) {
+      lines.push( match next_threshold( experience.level ) {
+        Some( threshold ) => format!( "Level   {} ({}/{} XP)", experience.level
+                                    , experience.points, threshold ),
+        None => format!( "Level   {} ({} XP)", experience.level, experience.points )
+      } );
+    }
+    
+    if let Some( mana ) = world.entities.mana.get( player ) {
+      lines.push( format!( "Mana    {}/{}", mana.current, mana.max ) );
+    }
+    
+    lines.push( String::new() );
+    
+    if let Some( skills ) = world.entities.skills.get( player ) {
+      for &kind in SKILL_KINDS.iter() {
+        lines.push( format!( "{:<10} {:>2}  ({}%)", kind.description().name()
+                           , skills.level( kind ), skills.chance( kind ) ) );
+      }
+    }
+    
+    ctx.print_frame( 2, 2, 40, lines.len() as i32 + 2, true
+                   , BackgroundFlag::Set, Some( "Character" ) );
+    
+    for (i, line) in lines.iter().enumerate() {
+      ctx.print_ex( 3, 3 + i as i32, BackgroundFlag::None, TextAlignment::Left, line );
+    }
+  }
+  
+  fn render_journal<C : Console>( ctx : &mut C, world : &World ) {
+    let mut lines = Vec::new();
+    
+    for id in world.journal.started() {
+      let quest = quest( id );
+      let status = world.journal.status( id );
+      
+      if !lines.is_empty() {
+        lines.push( String::new() );
+      }
+      
+      lines.push( match status {
+        QuestStatus::Completed => format!( "{} (completed)", quest.description().name() ),
+        _ => quest.description().name().to_string()
+      } );
+      
+      for (i, objective) in quest.objectives.iter().enumerate() {
+        let progress = world.journal.progress( id, i );
+        let target = objective.goal.target();
+        let mark = if progress >= target { 'x' } else { ' ' };
+        
+        lines.push( if target > 1 {
+          format!( " [{}] {} ({}/{})", mark, objective.text, progress, target )
+        } else {
+          format!( " [{}] {}", mark, objective.text )
+        } );
+      }
+    }
+    
+    if lines.is_empty() {
+      lines.push( "You haven't taken on any quests.".to_string() );
+    }
+    
+    ctx.print_frame( 2, 2, 50, lines.len() as i32 + 2, true
+                   , BackgroundFlag::Set, Some( "Journal" ) );
+    
+    for (i, line) in lines.iter().enumerate() {
+      ctx.print_ex( 3, 3 + i as i32, BackgroundFlag::None, TextAlignment::Left, line );
+    }
+  }
+  
+  // Waits for the character sheet or the journal to be closed
+  fn update_info_screen( &mut self, game : &Game ) -> Option<InputState> {
+    use ::tcod::input::KeyCode::Escape;
+    
+    while let Some( key ) = game.root.check_for_keypress( KEY_PRESSED ) {
+      if key.code == Escape {
+        return Some( InputState::Toplevel )
+      }
+    }
+    
+    None
+  }
+  
+  fn interaction_key( key : Key ) -> Option<Interaction> {
+    use ::tcod::input::KeyCode::Char;
+    
+    if key.code != Char {
+      return None
+    }
+    
+    Some( match key.printable {
+      'o' => Interaction::Open,
+      'c' => Interaction::Close,
+      _ => return None
+    } )
+  }
+  
   fn directional_key( key : Key ) -> Option<Direction> {
     use ::tcod::input::KeyCode::*;
     use util::Direction::*;
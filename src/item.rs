@@ -0,0 +1,156 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use tcod::Color;
+
+use util::*;
+use entity::RangedWeapon;
+use equipment::{EquipSlot, Equippable};
+use status::StatusKind;
+
+/// What happens to whoever uses up a consumable item.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UseEffect {
+  Heal( u32 ),
+  /// Fills the stomach with the given amount of food
+  Feed( u32 ),
+  /// Applies the status effect for the given duration
+  Status( StatusKind, u32 ),
+  /// Moves the user to a random spot on the map
+  Teleport,
+  /// Reveals the whole map
+  MapLevel,
+  /// Identifies everything the user carries
+  Identify
+}
+
+/// Lets an item be used up for its effect.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Consumable {
+  pub effect : UseEffect,
+  /// How using the item is described, like "drink" or "read"
+  pub verb   : String
+}
+
+/// Everything needed to spawn an item of a certain kind.
+#[derive(Clone)]
+pub struct ItemTemplate {
+  pub symbol       : char,
+  pub color        : Color,
+  pub ranged       : Option<RangedWeapon>,
+  pub equip        : Option<Equippable>,
+  pub consumable   : Option<Consumable>,
+  /// The name the item goes by until its kind is identified
+  pub unidentified : Option<String>,
+  /// What the item costs in a shop, if it can be bought and sold at all
+  pub price        : Option<u32>,
+  /// The gold a pile of it is worth, for gold lying around
+  pub gold         : Option<u32>
+}
+
+#[derive(RustcDecodable)]
+struct ConsumableConfig {
+  effect   : String,
+  verb     : Option<String>,
+  amount   : Option<u32>,
+  status   : Option<String>,
+  duration : Option<u32>
+}
+
+impl ConsumableConfig {
+  fn into_consumable( self ) -> Option<Consumable> {
+    let effect = match &self.effect[..] {
+      "heal" => UseEffect::Heal( self.amount.unwrap_or( 0 ) ),
+      "feed" => UseEffect::Feed( self.amount.unwrap_or( 0 ) ),
+      "status" => match self.status.as_ref().and_then( |s| StatusKind::from_config( s ) ) {
+        Some( kind ) => UseEffect::Status( kind, self.duration.unwrap_or( 0 ) ),
+        None => return None
+      },
+      "teleport" => UseEffect::Teleport,
+      "map" => UseEffect::MapLevel,
+      "identify" => UseEffect::Identify,
+      _ => return None
+    };
+    
+    Some( Consumable {
+      effect: effect,
+      verb: self.verb.unwrap_or( "use".to_string() )
+    } )
+  }
+}
+
+#[derive(RustcDecodable)]
+struct EquipConfig {
+  slot    : String,
+  attack  : Option<u32>,
+  defense : Option<u32>,
+  speed   : Option<i32>
+}
+
+#[derive(RustcDecodable)]
+struct ItemConfig {
+  symbol : char,
+  color  : Rgb,
+  ranged : Option<RangedWeapon>,
+  equip  : Option<EquipConfig>,
+  consumable   : Option<ConsumableConfig>,
+  unidentified : Option<String>,
+  price        : Option<u32>,
+  gold         : Option<u32>
+}
+
+thread_local!( static ITEMS : RefCell<HashMap<String, ItemTemplate>> =
+  RefCell::new( HashMap::new() ) );
+
+/// Loads the item templates from `data/item.toml`. Their names and
+/// descriptions are loaded from the same file along with the other
+/// descriptions.
+pub fn load_items() {
+  use toml::decode;
+  
+  let filename = "data/item.toml";
+  let data = load_data_file( filename );
+  
+  ITEMS.with( |items| {
+    let mut i = items.borrow_mut();
+    
+    for (entry_name, entry_value) in data.into_iter() {
+      let config : ItemConfig = decode( entry_value )
+        .expect( &format!( "Invalid item `{}` in '{}'", entry_name, filename ) );
+      
+      let equip = config.equip.map( |equip| {
+        let slots = EquipSlot::from_config( &equip.slot )
+          .expect( &format!( "Invalid equipment slot `{}` for item `{}` in '{}'"
+                           , equip.slot, entry_name, filename ) );
+        
+        Equippable {
+          slots: slots,
+          attack: equip.attack.unwrap_or( 0 ),
+          defense: equip.defense.unwrap_or( 0 ),
+          speed: equip.speed.unwrap_or( 0 )
+        }
+      } );
+      
+      let consumable = config.consumable.map( |c| {
+        c.into_consumable()
+          .expect( &format!( "Invalid consumable effect for item `{}` in '{}'"
+                           , entry_name, filename ) )
+      } );
+      
+      i.insert( entry_name, ItemTemplate {
+        symbol: config.symbol,
+        color: config.color.into(),
+        ranged: config.ranged,
+        equip: equip,
+        consumable: consumable,
+        unidentified: config.unidentified,
+        price: config.price,
+        gold: config.gold
+      } );
+    }
+  } );
+}
+
+pub fn item_template( kind : &str ) -> Option<ItemTemplate> {
+  ITEMS.with( |items| items.borrow().get( kind ).cloned() )
+}
@@ -0,0 +1,107 @@
+use tcod::Color;
+
+use map::*;
+use util::*;
+
+/// The light every tile receives even when no light source reaches it.
+pub const AMBIENT_LIGHT : Color = Color { r: 24, g: 24, b: 32 };
+
+/// The light explored tiles out of sight are drawn with.
+pub const REMEMBERED_LIGHT : Color = Color { r: 40, g: 40, b: 64 };
+
+/// How bright a tile has to be before actors standing on it can be seen.
+pub const MIN_VISIBLE_BRIGHTNESS : f32 = 0.2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LightSource {
+  pub radius : u32,
+  pub color  : Color
+}
+
+impl LightSource {
+  pub fn new( radius : u32, color : Color ) -> LightSource {
+    LightSource {
+      radius: radius,
+      color:  color
+    }
+  }
+  
+  // The share of the light's colour that reaches a tile `distance` away
+  fn falloff( &self, distance : f32 ) -> f32 {
+    let radius = self.radius as f32 + 1.0;
+    
+    if distance >= radius { 0.0 } else { 1.0 - distance / radius }
+  }
+}
+
+/// The light falling on every tile of the map, along with which tiles the
+/// player can currently see.
+pub struct LightMap {
+  width   : usize,
+  light   : Vec<Color>,
+  visible : Vec<bool>
+}
+
+impl LightMap {
+  pub fn new( width : usize, height : usize ) -> LightMap {
+    LightMap {
+      width:   width,
+      light:   vec![ AMBIENT_LIGHT; width * height ],
+      visible: vec![ false; width * height ]
+    }
+  }
+  
+  pub fn clear( &mut self ) {
+    for l in self.light.iter_mut() {
+      *l = AMBIENT_LIGHT;
+    }
+    
+    for v in self.visible.iter_mut() {
+      *v = false;
+    }
+  }
+  
+  fn index( &self, pos : Position ) -> usize {
+    pos.x as usize + pos.y as usize * self.width
+  }
+  
+  /// Lights up every tile the light source at `pos` can reach. Light is
+  /// blocked by opaque tiles.
+  pub fn add_light( &mut self, map : &Map, pos : Position, light : LightSource ) {
+    for lit in map.visible_from( pos, light.radius ) {
+      let dx = lit.x as f32 - pos.x as f32;
+      let dy = lit.y as f32 - pos.y as f32;
+      let amount = light.falloff( ( dx * dx + dy * dy ).sqrt() );
+      
+      if amount > 0.0 {
+        let idx = self.index( lit );
+        self.light[ idx ] = self.light[ idx ] + light.color * amount;
+      }
+    }
+  }
+  
+  /// Marks the tiles seen from `pos` as visible.
+  pub fn add_sight( &mut self, map : &Map, pos : Position, radius : u32 ) {
+    for seen in map.visible_from( pos, radius ) {
+      let idx = self.index( seen );
+      self.visible[ idx ] = true;
+    }
+  }
+  
+  pub fn light_at( &self, pos : Position ) -> Color {
+    self.light[ self.index( pos ) ]
+  }
+  
+  /// The brightness of the light at the given position, from 0 to 1.
+  pub fn brightness_at( &self, pos : Position ) -> f32 {
+    use std::cmp::max;
+    
+    let l = self.light_at( pos );
+    
+    max( l.r, max( l.g, l.b ) ) as f32 / 255.0
+  }
+  
+  pub fn is_visible( &self, pos : Position ) -> bool {
+    self.visible[ self.index( pos ) ]
+  }
+}
@@ -1,99 +1,288 @@
-use std::io::{BufReader, BufRead};
-use std::io;
-use std::path::Path;
-use std::fs::File;
-use std::cmp::{min, max};
-
-use tcod::Console;
-
-pub type Message = (String, u32);
-
-pub const MAX_MESSAGES : usize = 100;
-
-pub struct MessageLog {
-  messages : Vec<Message>,
-  log_file : Option<File>,
-  scroll   : usize
-}
-
-impl MessageLog {
-  pub fn new() -> MessageLog {
-    MessageLog {
-      messages: Vec::new(),
-      log_file: None,
-      scroll  : 0
-    }
-  }
-  
-  pub fn from_file( p : &Path ) -> Result<MessageLog, io::Error> {
-    let f = try!( File::open( p ) );
-    
-    let mut log = MessageLog {
-      messages: Vec::new(),
-      log_file: Some( f ),
-      scroll  : 0
-    };
-    
-    try!( log.load_messages() );
-    
-    Ok( log )
-  }
-  
-  fn load_messages( &mut self ) -> Result<(), io::Error> {
-    if !self.messages.is_empty() {
-      self.messages.drain( .. );
-    }
-    
-    let log_file = self.log_file.as_mut().expect(
-      "tried to read messages from a log file which isn't bound to a file" );
-    
-    let reader = BufReader::new( log_file );
-    
-    for line in reader.lines() {
-      self.messages.push( (try!( line ), 1) );
-    }
-    
-    Ok( () )
-  }
-  
-  pub fn add_message( &mut self, msg : String ) {
-    if !self.messages.is_empty() {
-      let lidx = self.messages.len() - 1;
-      let elm = &mut self.messages[lidx];
-      if elm.0 == msg {
-        elm.1 += 1;
-        return
-      }
-    }
-    
-    self.messages.push( (msg, 1) );
-  }
-  
-  pub fn render<C : Console>( &self, ctx : &mut C ) {
-    ctx.clear();
-    
-    if self.messages.is_empty() {
-      return;
-    }
-    
-    let width = ctx.width();
-    let height = ctx.height() as usize;
-    
-    let last_idx = self.messages.len();
-    
-    let first_item_dx =
-      max( last_idx as isize - height as isize - self.scroll as isize, 0 ) as usize;
-    
-    let last_item_idx = min( first_item_dx + height, last_idx );
-    
-    let to_be_shown = &self.messages[first_item_dx..last_item_idx];
-    
-    for (y, message) in to_be_shown.iter().enumerate() {
-      match message {
-        &(ref m, 1) => ctx.print( 0, y as i32, m ),
-        &(ref m, x) => ctx.print( 0, y as i32, format!( "{} x{}", m, x ) )
-      }
-    }
-  }
-}
-
+use std::io::{BufReader, BufRead};
+use std::io;
+use std::path::Path;
+use std::fs::File;
+use std::cmp::{min, max};
+
+use tcod::Console;
+
+use description::*;
+use event::*;
+use world::World;
+use entity::Entity;
+use item::UseEffect;
+use hunger::HungerStage;
+use util::capitalize;
+use spell::spell;
+use quest::quest;
+use trap::TrapKind;
+
+pub type Message = (String, u32);
+
+pub const MAX_MESSAGES : usize = 100;
+
+pub struct MessageLog {
+  messages : Vec<Message>,
+  // The number of messages added so far, counting repeats
+  added    : usize,
+  log_file : Option<File>,
+  scroll   : usize
+}
+
+impl MessageLog {
+  pub fn new() -> MessageLog {
+    MessageLog {
+      messages: Vec::new(),
+      added   : 0,
+      log_file: None,
+      scroll  : 0
+    }
+  }
+  
+  pub fn from_file( p : &Path ) -> Result<MessageLog, io::Error> {
+    let f = try!( File::open( p ) );
+    
+    let mut log = MessageLog {
+      messages: Vec::new(),
+      added   : 0,
+      log_file: Some( f ),
+      scroll  : 0
+    };
+    
+    try!( log.load_messages() );
+    
+    Ok( log )
+  }
+  
+  fn load_messages( &mut self ) -> Result<(), io::Error> {
+    if !self.messages.is_empty() {
+      self.messages.drain( .. );
+    }
+    
+    let log_file = self.log_file.as_mut().expect(
+      "tried to read messages from a log file which isn't bound to a file" );
+    
+    let reader = BufReader::new( log_file );
+    
+    for line in reader.lines() {
+      self.messages.push( (try!( line ), 1) );
+    }
+    
+    Ok( () )
+  }
+  
+  /// The number of messages that have been added to the log, counting
+  /// repeated messages.
+  pub fn added_count( &self ) -> usize {
+    self.added
+  }
+  
+  pub fn add_message( &mut self, msg : String ) {
+    self.added += 1;
+    
+    if !self.messages.is_empty() {
+      let lidx = self.messages.len() - 1;
+      let elm = &mut self.messages[lidx];
+      if elm.0 == msg {
+        elm.1 += 1;
+        return
+      }
+    }
+    
+    self.messages.push( (msg, 1) );
+  }
+  
+  pub fn render<C : Console>( &self, ctx : &mut C ) {
+    ctx.clear();
+    
+    if self.messages.is_empty() {
+      return;
+    }
+    
+    let width = ctx.width();
+    let height = ctx.height() as usize;
+    
+    let last_idx = self.messages.len();
+    
+    let first_item_dx =
+      max( last_idx as isize - height as isize - self.scroll as isize, 0 ) as usize;
+    
+    let last_item_idx = min( first_item_dx + height, last_idx );
+    
+    let to_be_shown = &self.messages[first_item_dx..last_item_idx];
+    
+    for (y, message) in to_be_shown.iter().enumerate() {
+      match message {
+        &(ref m, 1) => ctx.print( 0, y as i32, m ),
+        &(ref m, x) => ctx.print( 0, y as i32, format!( "{} x{}", m, x ) )
+      }
+    }
+  }
+}
+
+
+// The name of the entity as used in the middle of a sentence
+fn name_of( world : &World, e : Entity ) -> String {
+  if e == world.player() {
+    "you".to_string()
+  } else {
+    world.name_of( e )
+  }
+}
+
+// Whether the player would notice what the entity does: it's the player, or
+// it stands where the player can see
+fn in_view( world : &World, e : Entity ) -> bool {
+  e == world.player()
+    || world.entities.position( e ).map( |p| world.can_see( p ) ).unwrap_or( false )
+}
+
+// What the player notices when a consumable's effect hits them
+fn effect_message( effect : UseEffect ) -> String {
+  match effect {
+    UseEffect::Heal( _ ) =>
+      "You feel better.".to_string(),
+    UseEffect::Feed( _ ) =>
+      "That hit the spot.".to_string(),
+    UseEffect::Status( kind, _ ) =>
+      format!( "You are {}!", kind.description().name() ),
+    UseEffect::Teleport =>
+      "You find yourself somewhere else!".to_string(),
+    UseEffect::MapLevel =>
+      "The layout of the level becomes clear to you.".to_string(),
+    UseEffect::Identify =>
+      "You understand your belongings better.".to_string()
+  }
+}
+
+impl EventListener for MessageLog {
+  fn on_event( &mut self, world : &World, event : &Event ) {
+    use event::Event::*;
+    
+    let player = world.player();
+    
+    let msg = match *event {
+      ActionFailed { entity, reason } if entity == player =>
+        reason.message( world ),
+      Attacked { attacker, target, damage } if in_view( world, attacker )
+                                            || in_view( world, target ) => {
+        let verb = if attacker == player { "hit" } else { "hits" };
+        
+        format!( "{} {} {} for {} damage."
+               , capitalize( &name_of( world, attacker ) ), verb
+               , name_of( world, target ), damage )
+      },
+      Missed { attacker, target } if in_view( world, attacker ) || in_view( world, target ) => {
+        let verb = if attacker == player { "miss" } else { "misses" };
+        
+        format!( "{} {} {}.", capitalize( &name_of( world, attacker ) ), verb
+               , name_of( world, target ) )
+      },
+      Died { entity, .. } if entity == player =>
+        format!( "You die... Farewell, {}.", world.character.name ),
+      Died { entity, .. } if in_view( world, entity ) =>
+        format!( "{} dies.", capitalize( &name_of( world, entity ) ) ),
+      PickedUp { entity, item } if entity == player && world.entities.purses.has( item ) =>
+        format!( "You pick up {} gold.", world.entities.purses.get( item ).map( |p| p.gold ).unwrap_or( 0 ) ),
+      PickedUp { entity, item } if entity == player && world.entities.for_sale.has( item ) =>
+        format!( "You pick up {} (for sale, {} gold).", name_of( world, item )
+               , world.entities.prices.get( item ).map( |p| p.0 ).unwrap_or( 0 ) ),
+      Bought { entity, item, price } if entity == player =>
+        format!( "You buy {} for {} gold.", name_of( world, item ), price ),
+      Sold { entity, item, price } if entity == player =>
+        format!( "You sell {} for {} gold.", name_of( world, item ), price ),
+      Received { entity, item, from } if entity == player =>
+        format!( "{} gives you {}.", capitalize( &name_of( world, from ) ), name_of( world, item ) ),
+      HandedOver { entity, item, to } if entity == player =>
+        format!( "You give {} to {}.", name_of( world, item ), name_of( world, to ) ),
+      QuestStarted { quest: id } =>
+        format!( "New quest: {}.", quest( id ).description().name() ),
+      ObjectiveCompleted { quest: id, objective } =>
+        format!( "Objective complete: {}.", quest( id ).objectives[ objective ].text ),
+      QuestCompleted { quest: id } =>
+        format!( "Quest complete: {}!", quest( id ).description().name() ),
+      Rewarded { entity, item } if entity == player =>
+        format!( "You receive {} as a reward.", name_of( world, item ) ),
+      TurnedHostile { entity } =>
+        format!( "{} turns on you!", capitalize( &name_of( world, entity ) ) ),
+      Robbed { keeper, .. } =>
+        format!( "{} shouts: \"Stop, thief!\"", capitalize( &name_of( world, keeper ) ) ),
+      SwappedPlaces { entity, other } if entity == player =>
+        format!( "You swap places with {}.", name_of( world, other ) ),
+      PickedUp { entity, item } if entity == player =>
+        format!( "You pick up {}.", name_of( world, item ) ),
+      Fired { entity, weapon, hit } if entity == player => {
+        let thrown = world.entities.ranged_weapons.get( weapon )
+          .map( |r| r.thrown )
+          .unwrap_or( false );
+        
+        let verb = if thrown { "throw" } else { "shoot" };
+        
+        match hit {
+          Some( _ ) => format!( "You {} {}.", verb, name_of( world, weapon ) ),
+          None => format!( "You {} {}, but miss.", verb, name_of( world, weapon ) )
+        }
+      },
+      Equipped { entity, item } if entity == player =>
+        format!( "You equip {}.", name_of( world, item ) ),
+      Unequipped { entity, item } if entity == player =>
+        format!( "You take off {}.", name_of( world, item ) ),
+      Used { entity, item, effect } if entity == player => {
+        let verb = world.entities.consumables.get( item )
+          .map( |c| c.verb.clone() )
+          .unwrap_or( "use".to_string() );
+        
+        format!( "You {} {}. {}", verb, name_of( world, item ), effect_message( effect ) )
+      },
+      HungerChanged { entity, stage } if entity == player =>
+        match stage {
+          HungerStage::Fed => "You no longer feel hungry.",
+          HungerStage::Hungry => "You are getting hungry.",
+          HungerStage::Weak => "You feel weak from hunger.",
+          HungerStage::Fainting => "You are fainting from hunger!",
+          HungerStage::Starving => "You are starving to death!"
+        }.to_string(),
+      LevelledUp { entity, level } if entity == player =>
+        format!( "Welcome to level {}!", level ),
+      SkillImproved { entity, skill, level } if entity == player =>
+        format!( "Your {} skill improves to {}.", skill.description().name().to_lowercase(), level ),
+      Cast { entity, spell: id, target } => {
+        let name = spell( id ).description().name().to_string();
+        let verb = if entity == player { "cast" } else { "casts" };
+        
+        match target {
+          Some( target ) if target != entity =>
+            format!( "{} {} {} at {}.", capitalize( &name_of( world, entity ) ), verb
+                   , name, name_of( world, target ) ),
+          _ => format!( "{} {} {}.", capitalize( &name_of( world, entity ) ), verb, name )
+        }
+      },
+      SpellFailed { entity, spell: id } if entity == player =>
+        format!( "You fumble the words of {}.", spell( id ).description().name() ),
+      SpellFailed { entity, .. } =>
+        format!( "{} mutters something, but nothing happens.", capitalize( &name_of( world, entity ) ) ),
+      WokeUp { entity } if world.entities.position( entity ).map( |p| world.can_see( p ) )
+                                                              .unwrap_or( false ) =>
+        format!( "{} wakes up.", capitalize( &name_of( world, entity ) ) ),
+      Searched { entity, found: 0 } if entity == player =>
+        "You search the area, but find nothing.".to_string(),
+      Searched { entity, found } if entity == player =>
+        format!( "You search the area and find {} hidden thing{}!"
+               , found, if found == 1 { "" } else { "s" } ),
+      TrapSprung { entity, trap } if entity == player => match trap {
+        TrapKind::Pit => "You fall into a pit!".to_string(),
+        TrapKind::Dart => "A poisoned dart shoots out at you!".to_string(),
+        TrapKind::Teleport => "You feel yourself yanked away!".to_string(),
+        TrapKind::Alarm => "An alarm blares all around!".to_string()
+      },
+      TrapSprung { entity, .. } if world.entities.position( entity ).map( |p| world.can_see( p ) )
+                                                                .unwrap_or( false ) =>
+        format!( "{} sets off a trap.", capitalize( &name_of( world, entity ) ) ),
+      LevelEntered { depth } =>
+        format!( "You enter level {}.", depth ),
+      _ => return
+    };
+    
+    self.add_message( msg );
+  }
+}
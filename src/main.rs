@@ -7,19 +7,46 @@ extern crate rustc_serialize;
 
 mod util;
 mod ui;
+mod hud;
 mod log;
 mod description;
 mod map;
+mod light;
 mod world;
+mod spatial;
+mod entity;
 mod actor;
-mod player;
+mod status;
+mod ai;
+mod monster;
+mod item;
+mod shop;
+mod equipment;
+mod hunger;
+mod experience;
+mod skill;
+mod faction;
+mod spell;
+mod event;
+mod pathfind;
+mod noise;
+mod trap;
+mod options;
+mod targeting;
 mod input;
+mod character;
+mod quest;
+mod dialogue;
 
 use ui::*;
+use hud::*;
 use log::*;
 use map::MapLoadingError;
 use world::*;
+use event::*;
 use input::*;
+use description::*;
+use character::*;
 
 use std::error::Error;
 use std::cell::RefCell;
@@ -32,16 +59,22 @@ struct Game {
   root  : RootConsole,
   input : RefCell<Input>,
   world : RefCell<World>,
-  message_log : RefCell<MessageLog>
+  message_log : RefCell<MessageLog>,
+  statistics : RefCell<Statistics>
 }
 
 impl Game {
-  fn new( title : String, root : RootConsole ) -> Game {
+  fn new( title : String, mut root : RootConsole, character : Character ) -> Game {
     use std::path::Path;
     
     let mut world;
     
-    match World::new( "data/test.toml" ) {
+    root.set_window_title( format!( "{} - {}", title, character.title() ) );
+    
+    let mut message_log = MessageLog::new();
+    message_log.add_message( format!( "Welcome, {}!", character.title() ) );
+    
+    match World::new( "data/test.toml", character ) {
       Result::Err( MapLoadingError::ParseIntError( err ) ) => panic!( "{:?}", err.description() ),
       Result::Err( err ) => panic!( "{:?}", err ),
       Result::Ok( w ) => world = w
@@ -52,25 +85,63 @@ impl Game {
       root: root,
       input: RefCell::new( Input::new() ),
       world: RefCell::new( world ),
-      message_log: RefCell::new( MessageLog::new() )
+      message_log: RefCell::new( message_log ),
+      statistics: RefCell::new( Statistics::new() )
     }
   }
-
+  
+  // Hands the events that happened in the world to everyone listening. The
+  // quests are moved along first, and what that sets off is handed out the
+  // next time around.
+  fn dispatch_events( &self ) {
+    let events = self.world.borrow().take_events();
+    
+    self.world.borrow_mut().track_quests( &events );
+    
+    let world = self.world.borrow();
+    
+    for event in events {
+      self.message_log.borrow_mut().on_event( &world, &event );
+      self.statistics.borrow_mut().on_event( &world, &event );
+    }
+  }
+  
   fn start( &mut self ) {
     let mut msg_log_console = tcod::console::Offscreen::new( 80, 10 );
+    let hud = Hud::new( (60, 1).into() );
     
     while !self.root.window_closed() {
+      self.dispatch_events();
       self.input.borrow_mut().update( self );
+      self.dispatch_events();
       
       while self.world.borrow().player_is_performing_action() {
         self.world.borrow_mut().update();
+        self.dispatch_events();
       }
       
+      self.world.borrow_mut().update_lighting();
+      
       self.root.clear();
       self.world.borrow().render( &mut self.root );
+      {
+        let world = self.world.borrow();
+        self.input.borrow().render( &mut self.root, &world );
+        hud.render( &mut self.root, &world, world.player() );
+      }
       self.message_log.borrow_mut().render( &mut msg_log_console );
       tcod::console::blit( &msg_log_console, (0, 0), (0, 0), &mut self.root, (0, 40), 1.0, 1.0 );
       self.root.flush();
+      
+      let player_alive = {
+        let world = self.world.borrow();
+        world.is_alive( world.player() )
+      };
+      
+      if !player_alive {
+        self.root.wait_for_keypress( true );
+        break;
+      }
     }
   }
 
@@ -118,11 +189,84 @@ fn starting_menu( title : &str, root : &mut RootConsole ) -> MenuChoice {
   MenuChoice::Exit
 }
 
+// Walks the player through naming their character and picking its class and
+// background. Returns `None` if the window is closed on the way.
+fn create_character( root : &mut RootConsole ) -> Option<Character> {
+  let heading = TextField::new( "Create your character".to_string()
+                              , (root.width() / 2, 2).into()
+                              , TextAlignment::Center );
+  
+  let mut name_input = TextInput::new( "Name: ".to_string()
+                                     , (root.width() / 2 - 13, 5).into()
+                                     , 20 );
+  let mut name = None;
+  
+  while name.is_none() {
+    if root.window_closed() {
+      return None
+    }
+    
+    root.clear();
+    heading.render( root );
+    name_input.render( root );
+    root.flush();
+    
+    name = name_input.update( root );
+  }
+  
+  let class = match pick_character_option( root, "Choose your class", &classes() ) {
+    Some( class ) => class,
+    None => return None
+  };
+  
+  let background = match pick_character_option( root, "Choose your background"
+                                              , &backgrounds() ) {
+    Some( background ) => background,
+    None => return None
+  };
+  
+  Some( Character::new( name.unwrap(), class, background ) )
+}
+
+// Lets the player pick one of the options, showing the description of the
+// one currently selected
+fn pick_character_option( root : &mut RootConsole, heading : &str
+                        , options : &[CharacterOption] ) -> Option<CharacterOption> {
+  
+  let heading = TextField::new( heading.to_string()
+                              , (root.width() / 2, 2).into()
+                              , TextAlignment::Center );
+  
+  let names = options.iter().map( |o| o.description().name().to_string() ).collect();
+  let mut list = SelectionList::new( names, (root.width() / 2, 5).into()
+                                   , true, TextAlignment::Center );
+  
+  while !root.window_closed() {
+    let width = root.width();
+    
+    root.clear();
+    heading.render( root );
+    list.render( root );
+    
+    let selected = &options[ list.get_selection() ];
+    root.print_rect( width / 2 - 25, 7 + options.len() as i32, 50, 6
+                   , selected.description().description() );
+    
+    root.flush();
+    
+    if let Some( idx ) = list.update( root ) {
+      return Some( options[ idx ].clone() )
+    }
+  }
+  
+  None
+}
+
 fn main() {
   use std::path::Path;
   
   let title = format!( "Atina v{}", version!() );
-
+  
   let mut root = RootConsole::initializer()
     .size( 80, 50 )
     .title( &title )
@@ -131,10 +275,23 @@ fn main() {
   
   tcod::system::set_fps( 60 );
   
+  options::load_options();
   description::load_descriptions();
+  map::load_tile_graphics();
+  faction::load_factions();
+  monster::load_monsters();
+  item::load_items();
+  character::load_character_options();
+  experience::load_levels();
+  skill::load_skills();
+  spell::load_spells();
+  quest::load_quests();
+  dialogue::load_dialogues();
   
   if starting_menu( &title, &mut root ) == MenuChoice::StartGame {
-    let mut game = Game::new( title, root );
-    game.start();
+    if let Some( character ) = create_character( &mut root ) {
+      let mut game = Game::new( title, root, character );
+      game.start();
+    }
   }
 }
\ No newline at end of file
@@ -3,13 +3,18 @@ use std::io::Read;
 use std::num;
 use std::path::Path;
 use std::fs::File;
+use std::cell::RefCell;
+use std::collections::HashMap;
 
 use rustc_serialize::Decodable;
 
-use tcod::{Console};
+use tcod::{Console, Color};
+use tcod::map::{Map as FovMap, FovAlgorithm};
 
 use util::*;
 use description::*;
+use light::*;
+use trap::TrapKind;
 
 use self::Tile::*;
 
@@ -22,7 +27,9 @@ pub enum MapLoadingError {
   InvalidWidth( usize, usize ),
   InvalidHeight( usize, usize ),
   InvalidTile( char ),
-  NoPlayerPosition
+  NoPlayerPosition,
+  /// There are more shops than rooms that could hold them
+  NoRoomForShop
 }
 
 impl From<io::Error> for MapLoadingError {
@@ -37,13 +44,54 @@ impl From<num::ParseIntError> for MapLoadingError {
   }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Tile {
   Ground,
   Floor,
   Wall,
   Rock,
   Tree,
+  ClosedDoor,
+  OpenDoor,
+  Trap( TrapKind ),
+  ShallowWater,
+  DeepWater,
+  Lava,
+  Rubble,
+  Undergrowth
+}
+
+/// How an actor gets around, deciding which tiles it can cross and how long
+/// that takes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MovementType {
+  Walking,
+  /// Crosses water, deep or not, as easily as ground
+  Swimming,
+  /// Passes over anything that isn't solid without slowing down
+  Flying
+}
+
+impl MovementType {
+  pub fn from_config( name : &str ) -> Option<MovementType> {
+    Some( match name {
+      "walk" => MovementType::Walking,
+      "swim" => MovementType::Swimming,
+      "fly"  => MovementType::Flying,
+      _      => return None
+    } )
+  }
+}
+
+/// The movement cost of a tile that's as easy to cross as plain ground, as a
+/// percentage of the time a step takes.
+pub const NORMAL_MOVEMENT_COST : u32 = 100;
+
+/// The ways an actor can interact with a tile.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Interaction {
+  Open,
+  Close
 }
 
 impl Into<char> for Tile {
@@ -55,7 +103,13 @@ impl Into<char> for Tile {
       Floor => ' ',
       Wall => '#',
       Rock => chars::BLOCK1,
-      Tree => chars::CLUB
+      Tree => chars::CLUB,
+      ClosedDoor => '+',
+      OpenDoor => '\'',
+      Trap( _ ) => '^',
+      ShallowWater | DeepWater | Lava => '~',
+      Rubble => ':',
+      Undergrowth => '"'
     }
   }
 }
@@ -67,16 +121,127 @@ impl Describe for Tile {
       Floor => "tile.floor",
       Wall => "tile.wall",
       Rock => "tile.rock",
-      Tree => "tile.tree"
+      Tree => "tile.tree",
+      ClosedDoor => "tile.closed_door",
+      OpenDoor => "tile.open_door",
+      Trap( TrapKind::Pit ) => "tile.pit_trap",
+      Trap( TrapKind::Dart ) => "tile.dart_trap",
+      Trap( TrapKind::Teleport ) => "tile.teleport_trap",
+      Trap( TrapKind::Alarm ) => "tile.alarm_trap",
+      ShallowWater => "tile.shallow_water",
+      DeepWater => "tile.deep_water",
+      Lava => "tile.lava",
+      Rubble => "tile.rubble",
+      Undergrowth => "tile.undergrowth"
     }.to_string()
   }
 }
 
+/// The colours a tile is drawn with.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TileGraphics {
+  pub fg : Color,
+  pub bg : Color
+}
+
+#[derive(RustcDecodable)]
+struct TileGraphicsConfig {
+  fg : Rgb,
+  bg : Rgb
+}
+
+thread_local!( static TILE_GRAPHICS : RefCell<HashMap<String, TileGraphics>> =
+  RefCell::new( HashMap::new() ) );
+
+/// Loads the colours of every tile from `data/tile_graphics.toml`, keyed by
+/// the same names as the tile descriptions.
+pub fn load_tile_graphics() {
+  use toml::decode;
+  
+  let filename = "data/tile_graphics.toml";
+  let data = load_data_file( filename );
+  
+  TILE_GRAPHICS.with( |graphics| {
+    let mut g = graphics.borrow_mut();
+    
+    for (entry_name, entry_value) in data.into_iter() {
+      let config : TileGraphicsConfig = decode( entry_value )
+        .expect( &format!( "Invalid tile graphics `{}` in '{}'"
+                         , entry_name, filename ) );
+      
+      g.insert( format!( "tile.{}", entry_name )
+              , TileGraphics { fg: config.fg.into(), bg: config.bg.into() } );
+    }
+  } );
+}
+
 impl Tile {
+  pub fn graphics( self ) -> TileGraphics {
+    let id = self.desc_id();
+    
+    TILE_GRAPHICS.with( |graphics| {
+      *graphics.borrow().get( &id )
+        .expect( &format!( "No entry `{}` was found in the tile graphics.", id ) )
+    } )
+  }
+  
   pub fn is_solid( self ) -> bool {
     match self {
-      Ground | Floor => false,
-      Wall | Rock | Tree => true
+      Ground | Floor | OpenDoor | Trap( _ ) => false,
+      ShallowWater | DeepWater | Lava | Rubble | Undergrowth => false,
+      Wall | Rock | Tree | ClosedDoor => true
+    }
+  }
+  
+  pub fn is_opaque( self ) -> bool {
+    match self {
+      Ground | Floor | OpenDoor | Trap( _ ) => false,
+      ShallowWater | DeepWater | Lava | Rubble | Undergrowth => false,
+      Wall | Rock | Tree | ClosedDoor => true
+    }
+  }
+  
+  /// The time it takes to step onto the tile moving the given way, as a
+  /// percentage of a normal step, or `None` if it can't be entered that way.
+  pub fn movement_cost( self, movement : MovementType ) -> Option<u32> {
+    use self::MovementType::*;
+    
+    if self.is_solid() {
+      return None
+    }
+    
+    Some( match (self, movement) {
+      (_, Flying) => NORMAL_MOVEMENT_COST,
+      (ShallowWater, Swimming) | (DeepWater, Swimming) => NORMAL_MOVEMENT_COST,
+      (DeepWater, _) | (Lava, _) => return None,
+      (ShallowWater, _) => 150,
+      (Undergrowth, _) => 150,
+      (Rubble, _) => 200,
+      _ => NORMAL_MOVEMENT_COST
+    } )
+  }
+  
+  pub fn is_door( self ) -> bool {
+    self == ClosedDoor || self == OpenDoor
+  }
+  
+  /// What the tile looks like while it's hidden: traps pass for floor and
+  /// secret doors for walls.
+  pub fn disguise( self ) -> Tile {
+    match self {
+      Trap( _ ) => Floor,
+      ClosedDoor | OpenDoor => Wall,
+      tile => tile
+    }
+  }
+  
+  /// Returns the tile this tile turns into when interacted with in the given
+  /// way, or `None` if the interaction doesn't apply to it.
+  pub fn interact( self, how : Interaction ) -> Option<Tile> {
+    match (self, how) {
+      (ClosedDoor, Interaction::Open) => Some( OpenDoor ),
+      (OpenDoor, Interaction::Close) => Some( ClosedDoor ),
+      _ => None
     }
   }
   
@@ -88,6 +253,13 @@ impl Tile {
       '#' => Wall,
       '+' => Rock,
       'T' => Tree,
+      'D' => ClosedDoor,
+      'd' => OpenDoor,
+      '~' => ShallowWater,
+      'W' => DeepWater,
+      'L' => Lava,
+      ':' => Rubble,
+      '"' => Undergrowth,
       _   => return None
     } )
   }
@@ -95,9 +267,60 @@ impl Tile {
 
 pub struct Map {
   tiles  : Vec<Tile>,
+  // Mirrors the opacity of the tiles, used to compute fields of view
+  fov    : RefCell<FovMap>,
   pub width  : usize,
   pub height : usize,
-  pub player_position : Position
+  pub player_position : Position,
+  pub lights : Vec<(Position, LightSource)>,
+  /// How many levels down the map lies
+  pub depth : u32,
+  /// The kind and position of each monster placed on the map
+  pub monsters : Vec<(String, Position)>,
+  /// The kind and position of each item lying on the map
+  pub items : Vec<(String, Position)>,
+  pub shops : Vec<ShopPlacement>,
+  // Whether the player has seen each tile
+  explored : Vec<bool>,
+  // Whether each tile is still hiding what it really is, like traps and
+  // secret doors
+  hidden : Vec<bool>
+}
+
+#[derive(RustcDecodable)]
+struct LightConfig {
+  position : Position,
+  radius   : u32,
+  color    : Rgb
+}
+
+#[derive(RustcDecodable)]
+struct TrapConfig {
+  kind     : String,
+  position : Position,
+  hidden   : Option<bool>
+}
+
+#[derive(RustcDecodable)]
+struct PlacementConfig {
+  kind     : String,
+  position : Position
+}
+
+/// A shop on the map: a shopkeeper of the kind `keeper` standing at
+/// `position`, and the items of `stock` laid out for sale in `area`.
+#[derive(Clone)]
+pub struct ShopPlacement {
+  pub keeper   : String,
+  pub position : Position,
+  pub area     : Area,
+  pub stock    : Vec<String>
+}
+
+#[derive(RustcDecodable)]
+struct ShopConfig {
+  keeper : String,
+  stock  : Vec<String>
 }
 
 #[derive(RustcDecodable)]
@@ -105,6 +328,12 @@ struct MapConfig {
   dimensions : Pos<usize>,
   player_position : Position,
   layout : String,
+  depth : Option<u32>,
+  lights : Option<Vec<LightConfig>>,
+  monsters : Option<Vec<PlacementConfig>>,
+  items : Option<Vec<PlacementConfig>>,
+  shops : Option<Vec<ShopConfig>>,
+  traps : Option<Vec<TrapConfig>>
 }
 
 impl Map {
@@ -124,6 +353,7 @@ impl Map {
     let lines : Vec<&str> = config.layout.lines().collect();
     let (width, height) = config.dimensions.into();
     let mut tiles = Vec::with_capacity( width * height );
+    let mut hidden = Vec::with_capacity( width * height );
     
     if lines.len() != height {
       panic!( "Failed to decode data file: '{}'", filename )
@@ -135,8 +365,16 @@ impl Map {
       }
       
       for (x, chr) in row.chars().enumerate() {
+        // Secret doors are closed doors that pass for walls until found
+        if chr == 'S' {
+          tiles.push( ClosedDoor );
+          hidden.push( true );
+          continue;
+        }
+        
         if let Some( tile ) = Tile::from_config( chr ) {
           tiles.push( tile );
+          hidden.push( false );
           continue;
         }
         
@@ -146,37 +384,442 @@ impl Map {
     
     assert_eq!( tiles.len(), width * height );
     
-    Ok( Map {
+    for trap in config.traps.unwrap_or( Vec::new() ) {
+      let kind = TrapKind::from_config( &trap.kind )
+        .expect( &format!( "Unknown trap kind `{}` in '{}'", trap.kind, filename ) );
+      let i = trap.position.x as usize + trap.position.y as usize * width;
+      
+      assert!( i < tiles.len(), "A trap in '{}' lies outside the map", filename );
+      
+      tiles[ i ] = Trap( kind );
+      hidden[ i ] = trap.hidden.unwrap_or( true );
+    }
+    
+    let mut fov = FovMap::new( width as i32, height as i32 );
+    
+    for (i, tile) in tiles.iter().enumerate() {
+      fov.set( (i % width) as i32, (i / width) as i32
+             , !tile.is_opaque(), !tile.is_solid() );
+    }
+    
+    let lights = config.lights.unwrap_or( Vec::new() ).into_iter()
+      .map( |l| (l.position, LightSource::new( l.radius, l.color.into() )) )
+      .collect();
+    
+    let monsters = config.monsters.unwrap_or( Vec::new() ).into_iter()
+      .map( |m| (m.kind, m.position) )
+      .collect();
+    
+    let items = config.items.unwrap_or( Vec::new() ).into_iter()
+      .map( |i| (i.kind, i.position) )
+      .collect();
+    
+    let mut map = Map {
       tiles: tiles,
+      fov: RefCell::new( fov ),
       width: width,
       height: height,
-      player_position: config.player_position
-    } )
+      player_position: config.player_position,
+      lights: lights,
+      depth: config.depth.unwrap_or( 1 ),
+      monsters: monsters,
+      items: items,
+      shops: Vec::new(),
+      explored: vec![ false; width * height ],
+      hidden: hidden
+    };
+    
+    // Each shop is set up in the next room that suits one
+    let shops = config.shops.unwrap_or( Vec::new() );
+    let rooms = map.shop_rooms();
+    
+    if shops.len() > rooms.len() {
+      return Err( NoRoomForShop )
+    }
+    
+    map.shops = shops.into_iter().zip( rooms.into_iter() )
+      .map( |(shop, (position, area))| ShopPlacement {
+        keeper: shop.keeper,
+        position: position,
+        area: area,
+        stock: shop.stock
+      } )
+      .collect();
+    
+    Ok( map )
+  }
+  
+  // The rooms a shop could be set up in: plain floor walled off all around
+  // but for a single door, with nothing else placed in it. Each is given
+  // along with the spot for the shopkeeper, next to the door but out of the
+  // doorway.
+  fn shop_rooms( &self ) -> Vec<(Position, Area)> {
+    let mut seen = vec![ false; self.width * self.height ];
+    let mut rooms = Vec::new();
+    
+    for i in 0..self.tiles.len() {
+      if seen[ i ] || self.tiles[ i ].is_solid() || self.tiles[ i ].is_door() {
+        continue;
+      }
+      
+      // Flood the room from here, noting the doors along its walls
+      let mut room = Vec::new();
+      let mut doors = Vec::new();
+      let mut open = vec![ Position::new( (i % self.width) as u32, (i / self.width) as u32 ) ];
+      seen[ i ] = true;
+      
+      while let Some( pos ) = open.pop() {
+        room.push( pos );
+        
+        for next in DIRECTIONS.iter().filter_map( |dir| dir.try_offset_position( pos, self ) ) {
+          let j = next.x as usize + next.y as usize * self.width;
+          
+          if self.tiles[ j ].is_door() {
+            if !doors.contains( &next ) {
+              doors.push( next );
+            }
+          } else if !seen[ j ] && !self.tiles[ j ].is_solid() {
+            seen[ j ] = true;
+            open.push( next );
+          }
+        }
+      }
+      
+      if let Some( shop_room ) = self.shop_room( &room, &doors ) {
+        rooms.push( shop_room );
+      }
+    }
+    
+    rooms
   }
   
+  // The spot for the shopkeeper and the area of the room, if it suits a shop
+  fn shop_room( &self, room : &[Position], doors : &[Position] ) -> Option<(Position, Area)> {
+    if doors.len() != 1 || self.is_hidden( doors[ 0 ] ) {
+      return None
+    }
+    
+    let door = doors[ 0 ];
+    
+    let x = room.iter().map( |p| p.x ).min().unwrap_or( 0 );
+    let y = room.iter().map( |p| p.y ).min().unwrap_or( 0 );
+    let area = Area {
+      x: x,
+      y: y,
+      width: room.iter().map( |p| p.x ).max().unwrap_or( 0 ) + 1 - x,
+      height: room.iter().map( |p| p.y ).max().unwrap_or( 0 ) + 1 - y
+    };
+    
+    let on_edge = |p : &Position| {
+      p.x == 0 || p.y == 0 || p.x as usize == self.width - 1 || p.y as usize == self.height - 1
+    };
+    
+    let taken = |p : &Position| {
+      *p == self.player_position
+      || self.monsters.iter().any( |&(_, m)| m == *p )
+      || self.items.iter().any( |&(_, i)| i == *p )
+    };
+    
+    // A shop needs room for the keeper and something to sell, and has to
+    // fill its walls so the keeper can tell when goods leave it
+    if room.len() < 2 || room.len() != ( area.width * area.height ) as usize
+       || room.iter().any( |p| self.tiles[ p.x as usize + p.y as usize * self.width ] != Floor )
+       || room.iter().any( |p| on_edge( p ) || taken( p ) ) {
+      return None
+    }
+    
+    // Standing to the side of the door leaves the way in free
+    let next_to_door : Vec<Position> = room.iter().cloned()
+      .filter( |p| p.distance( door ) == 1 )
+      .collect();
+    let keeper = next_to_door.iter()
+      .find( |p| p.x != door.x && p.y != door.y )
+      .or( next_to_door.first() )
+      .cloned();
+    
+    keeper.map( |keeper| (keeper, area) )
+  }
+  
+  /// The tile at `pos` as far as anyone can tell. Hidden tiles are given
+  /// as what they pass for.
   pub fn tile_at( &self, pos : Position ) -> Tile {
     let x = pos.x as usize;
     let y = pos.y as usize;
     
+    assert!( x < self.width && y < self.height
+           , "the given position is outside the map bounds" );
+    
+    let tile = self.tiles[ x + y * self.width ];
+    
+    if self.hidden[ x + y * self.width ] { tile.disguise() } else { tile }
+  }
+  
+  /// The tile at `pos` as it really is, hidden or not.
+  pub fn actual_tile_at( &self, pos : Position ) -> Tile {
+    let x = pos.x as usize;
+    let y = pos.y as usize;
+    
     assert!( x < self.width && y < self.height
            , "the given position is outside the map bounds" );
     
     self.tiles[ x + y * self.width ]
   }
   
-  pub fn render<C : Console>( &self, ctx : &mut C ) {
-    use ::tcod::colors::{WHITE, BLACK};
+  pub fn is_hidden( &self, pos : Position ) -> bool {
+    let x = pos.x as usize;
+    let y = pos.y as usize;
+    
+    assert!( x < self.width && y < self.height
+           , "the given position is outside the map bounds" );
+    
+    self.hidden[ x + y * self.width ]
+  }
+  
+  /// Shows the tile at `pos` for what it really is.
+  pub fn reveal( &mut self, pos : Position ) {
+    let x = pos.x as usize;
+    let y = pos.y as usize;
+    
+    assert!( x < self.width && y < self.height
+           , "the given position is outside the map bounds" );
+    
+    self.hidden[ x + y * self.width ] = false;
+  }
+  
+  pub fn set_tile( &mut self, pos : Position, tile : Tile ) {
+    let x = pos.x as usize;
+    let y = pos.y as usize;
+    
+    assert!( x < self.width && y < self.height
+           , "the given position is outside the map bounds" );
+    
+    self.tiles[ x + y * self.width ] = tile;
+    self.fov.borrow_mut().set( x as i32, y as i32
+                             , !tile.is_opaque(), !tile.is_solid() );
+  }
+  
+  pub fn is_explored( &self, pos : Position ) -> bool {
+    let x = pos.x as usize;
+    let y = pos.y as usize;
+    
+    assert!( x < self.width && y < self.height
+           , "the given position is outside the map bounds" );
+    
+    self.explored[ x + y * self.width ]
+  }
+  
+  /// Remembers the tile at `pos` as seen by the player.
+  pub fn explore( &mut self, pos : Position ) {
+    let x = pos.x as usize;
+    let y = pos.y as usize;
     
+    assert!( x < self.width && y < self.height
+           , "the given position is outside the map bounds" );
+    
+    self.explored[ x + y * self.width ] = true;
+  }
+  
+  /// Returns every position within `radius` that can be seen from `pos`,
+  /// looking past transparent tiles only. A radius of 0 is unlimited.
+  pub fn visible_from( &self, pos : Position, radius : u32 ) -> Vec<Position> {
+    use std::cmp::min;
+    
+    let mut fov = self.fov.borrow_mut();
+    fov.compute_fov( pos.x as i32, pos.y as i32, radius as i32
+                   , true, FovAlgorithm::Basic );
+    
+    let (x0, y0, x1, y1) = if radius == 0 {
+      (0, 0, self.width - 1, self.height - 1)
+    } else {
+      let r = radius as usize;
+      let (x, y) = (pos.x as usize, pos.y as usize);
+      
+      ( x.saturating_sub( r ), y.saturating_sub( r )
+      , min( x + r, self.width - 1 ), min( y + r, self.height - 1 ) )
+    };
+    
+    let mut visible = Vec::new();
+    
+    for y in y0..y1 + 1 {
+      for x in x0..x1 + 1 {
+        if fov.is_in_fov( x as i32, y as i32 ) {
+          visible.push( Position::new( x as u32, y as u32 ) );
+        }
+      }
+    }
+    
+    visible
+  }
+  
+  /// The positions on a straight line from `from` through `to`, not
+  /// including `from`. The line ends at `to`, or right before the first solid
+  /// tile in the way.
+  pub fn line( &self, from : Position, to : Position ) -> Vec<Position> {
+    let (x1, y1) = (to.x as i32, to.y as i32);
+    let (mut x, mut y) = (from.x as i32, from.y as i32);
+    
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    
+    let mut line = Vec::new();
+    
+    while (x, y) != (x1, y1) {
+      let e2 = 2 * err;
+      
+      if e2 >= dy {
+        err += dy;
+        x += sx;
+      }
+      
+      if e2 <= dx {
+        err += dx;
+        y += sy;
+      }
+      
+      let pos = Position::new( x as u32, y as u32 );
+      
+      if self.tile_at( pos ).is_solid() {
+        break;
+      }
+      
+      line.push( pos );
+    }
+    
+    line
+  }
+  
+  /// Draws the tiles the player can see, tinted by the light falling on
+  /// them, and the explored tiles out of sight as they're remembered.
+  pub fn render<C : Console>( &self, ctx : &mut C, lighting : &LightMap ) {
     let tile_poses = self.tiles
       .iter()
       .enumerate()
       .map( |(i, t)| ( (i % self.width, i / self.width), t) );
     
     for ((x, y), &tile) in tile_poses {
+      let pos = Position::new( x as u32, y as u32 );
+      let tile = if self.hidden[ x + y * self.width ] { tile.disguise() } else { tile };
+      
+      let light = if lighting.is_visible( pos ) {
+        lighting.light_at( pos )
+      } else if self.explored[ x + y * self.width ] {
+        REMEMBERED_LIGHT
+      } else {
+        continue;
+      };
+      
+      let graphics = tile.graphics();
+      
       ctx.put_char_ex( x as i32, y as i32
                      , tile.into()
-                     , WHITE, BLACK );
+                     , graphics.fg * light, graphics.bg * light );
     }
     
   }
 }
+
+#[cfg(test)]
+impl Map {
+  /// Builds a map from rows of tiles written as in the layout of a map file,
+  /// without anything placed on it.
+  pub fn from_rows( rows : &[&str] ) -> Map {
+    let width = rows[ 0 ].len();
+    let height = rows.len();
+    
+    let tiles : Vec<Tile> = rows.iter()
+      .flat_map( |row| row.chars() )
+      .map( |chr| Tile::from_config( chr ).expect( "invalid tile in test map" ) )
+      .collect();
+    
+    assert_eq!( tiles.len(), width * height );
+    
+    let mut fov = FovMap::new( width as i32, height as i32 );
+    
+    for (i, tile) in tiles.iter().enumerate() {
+      fov.set( (i % width) as i32, (i / width) as i32
+             , !tile.is_opaque(), !tile.is_solid() );
+    }
+    
+    Map {
+      tiles: tiles,
+      fov: RefCell::new( fov ),
+      width: width,
+      height: height,
+      player_position: Position::new( 0, 0 ),
+      lights: Vec::new(),
+      depth: 1,
+      monsters: Vec::new(),
+      items: Vec::new(),
+      shops: Vec::new(),
+      explored: vec![ false; width * height ],
+      hidden: vec![ false; width * height ]
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  
+  #[test]
+  fn shops_go_into_rooms_with_a_single_door() {
+    let map = Map::from_rows( &[ "########"
+                               , "#   #  #"
+                               , "#   D  #"
+                               , "#   #  #"
+                               , "########" ] );
+    let rooms = map.shop_rooms();
+    
+    assert_eq!( rooms.len(), 2 );
+    assert_eq!( rooms[ 0 ].1, Area { x: 1, y: 1, width: 3, height: 3 } );
+    assert_eq!( rooms[ 1 ].1, Area { x: 5, y: 1, width: 2, height: 3 } );
+    
+    // The keepers stand beside the door, not in front of it
+    for &(keeper, area) in &rooms {
+      assert!( area.contains( keeper ) );
+      assert_eq!( keeper.distance( Position::new( 4, 2 ) ), 1 );
+      assert!( keeper.y != 2 );
+    }
+  }
+  
+  #[test]
+  fn rooms_with_more_than_one_way_in_are_no_shops() {
+    let map = Map::from_rows( &[ "#######"
+                               , "#  #  #"
+                               , "D  D  #"
+                               , "#  #  #"
+                               , "#######" ] );
+    let rooms = map.shop_rooms();
+    
+    assert_eq!( rooms.len(), 1 );
+    assert_eq!( rooms[ 0 ].1, Area { x: 4, y: 1, width: 2, height: 3 } );
+  }
+  
+  #[test]
+  fn rooms_behind_secret_doors_are_no_shops() {
+    let mut map = Map::from_rows( &[ "#####"
+                                   , "#   #"
+                                   , "##D##"
+                                   , "#   #"
+                                   , "#####" ] );
+    map.hidden[ 2 * 5 + 2 ] = true;
+    
+    assert!( map.shop_rooms().is_empty() );
+  }
+  
+  #[test]
+  fn rooms_with_something_in_them_are_no_shops() {
+    let mut map = Map::from_rows( &[ "######"
+                                   , "#  # #"
+                                   , "#  D #"
+                                   , "#~ # #"
+                                   , "######" ] );
+    assert_eq!( map.shop_rooms().len(), 1 );
+    
+    map.player_position = Position::new( 4, 2 );
+    assert!( map.shop_rooms().is_empty() );
+  }
+}
@@ -0,0 +1,122 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use tcod::Color;
+
+use util::*;
+use light::LightSource;
+use ai::Behaviour;
+use faction::Faction;
+use map::MovementType;
+
+/// Everything needed to spawn a monster of a certain kind.
+#[derive(Clone)]
+pub struct MonsterTemplate {
+  pub symbol    : char,
+  pub color     : Color,
+  pub health    : u32,
+  pub speed     : u32,
+  pub attack    : u32,
+  pub defense   : u32,
+  pub behaviour : Behaviour,
+  pub faction   : Faction,
+  pub sight     : u32,
+  pub movement  : MovementType,
+  /// Whether the monster starts out asleep
+  pub asleep    : bool,
+  pub light     : Option<LightSource>,
+  /// The experience gained by killing the monster
+  pub experience : u32,
+  /// The skill levels the monster starts with, keyed by skill name
+  pub skills     : HashMap<String, u32>,
+  pub mana       : u32,
+  /// The names of the spells the monster knows
+  pub spells     : Vec<String>,
+  /// The name of the dialogue started by talking to the monster
+  pub dialogue   : Option<String>
+}
+
+#[derive(RustcDecodable)]
+struct GlowConfig {
+  radius : u32,
+  color  : Rgb
+}
+
+#[derive(RustcDecodable)]
+struct MonsterConfig {
+  symbol    : char,
+  color     : Rgb,
+  health    : u32,
+  speed     : u32,
+  attack    : u32,
+  defense   : u32,
+  behaviour : String,
+  faction   : String,
+  sight     : u32,
+  movement  : Option<String>,
+  asleep    : Option<bool>,
+  light     : Option<GlowConfig>,
+  experience : u32,
+  skills     : Option<HashMap<String, u32>>,
+  mana       : Option<u32>,
+  spells     : Option<Vec<String>>,
+  dialogue   : Option<String>
+}
+
+thread_local!( static MONSTERS : RefCell<HashMap<String, MonsterTemplate>> =
+  RefCell::new( HashMap::new() ) );
+
+/// Loads the monster templates from `data/monster.toml`. Their names and
+/// descriptions are loaded from the same file along with the other
+/// descriptions.
+pub fn load_monsters() {
+  use toml::decode;
+  
+  let filename = "data/monster.toml";
+  let data = load_data_file( filename );
+  
+  MONSTERS.with( |monsters| {
+    let mut m = monsters.borrow_mut();
+    
+    for (entry_name, entry_value) in data.into_iter() {
+      let config : MonsterConfig = decode( entry_value )
+        .expect( &format!( "Invalid monster `{}` in '{}'", entry_name, filename ) );
+      
+      let behaviour = Behaviour::from_config( &config.behaviour )
+        .expect( &format!( "Invalid behaviour `{}` for monster `{}`"
+                         , config.behaviour, entry_name ) );
+      let faction = Faction::from_config( &config.faction )
+        .expect( &format!( "Unknown faction `{}` for monster `{}`"
+                         , config.faction, entry_name ) );
+      let movement = match config.movement {
+        Some( ref name ) => MovementType::from_config( name )
+          .expect( &format!( "Invalid movement `{}` for monster `{}`", name, entry_name ) ),
+        None => MovementType::Walking
+      };
+      
+      m.insert( entry_name, MonsterTemplate {
+        symbol: config.symbol,
+        color: config.color.into(),
+        health: config.health,
+        speed: config.speed,
+        attack: config.attack,
+        defense: config.defense,
+        behaviour: behaviour,
+        faction: faction,
+        sight: config.sight,
+        movement: movement,
+        asleep: config.asleep.unwrap_or( false ),
+        light: config.light.map( |l| LightSource::new( l.radius, l.color.into() ) ),
+        experience: config.experience,
+        skills: config.skills.unwrap_or( HashMap::new() ),
+        mana: config.mana.unwrap_or( 0 ),
+        spells: config.spells.unwrap_or( Vec::new() ),
+        dialogue: config.dialogue
+      } );
+    }
+  } );
+}
+
+pub fn monster_template( kind : &str ) -> Option<MonsterTemplate> {
+  MONSTERS.with( |monsters| monsters.borrow().get( kind ).cloned() )
+}
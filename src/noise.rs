@@ -0,0 +1,106 @@
+use std::collections::{HashMap, VecDeque};
+
+use util::*;
+use map::Map;
+use pathfind::neighbours;
+
+/// The noise a step makes.
+pub const STEP_NOISE : u32 = 4;
+/// The noise opening or closing a door makes.
+pub const DOOR_NOISE : u32 = 6;
+/// The noise fighting makes, whether the blow lands or not.
+pub const FIGHT_NOISE : u32 = 8;
+/// The noise casting a spell makes.
+pub const SPELL_NOISE : u32 = 6;
+/// The noise a triggered alarm trap makes.
+pub const ALARM_NOISE : u32 = 20;
+
+/// How loud a noise has to be where a sleeping actor lies to wake it up.
+pub const WAKING_LOUDNESS : u32 = 3;
+
+/// How loud a noise of the given intensity made at `origin` is at each
+/// position it reaches. It loses a point of loudness with every step it
+/// spreads and doesn't go through solid tiles.
+pub fn spread( map : &Map, origin : Position, intensity : u32 ) -> HashMap<Position, u32> {
+  let mut loudness = HashMap::new();
+  let mut open = VecDeque::new();
+  
+  if intensity == 0 {
+    return loudness
+  }
+  
+  loudness.insert( origin, intensity );
+  open.push_back( origin );
+  
+  while let Some( pos ) = open.pop_front() {
+    let next_loudness = loudness[ &pos ] - 1;
+    
+    if next_loudness == 0 {
+      continue;
+    }
+    
+    for next in neighbours( map, pos ) {
+      if loudness.contains_key( &next ) || map.tile_at( next ).is_solid() {
+        continue;
+      }
+      
+      loudness.insert( next, next_loudness );
+      open.push_back( next );
+    }
+  }
+  
+  loudness
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  
+  #[test]
+  fn noise_fades_with_each_step() {
+    let map = Map::from_rows( &[ "      " ] );
+    let loudness = spread( &map, Position::new( 0, 0 ), 3 );
+    
+    assert_eq!( loudness.get( &Position::new( 0, 0 ) ), Some( &3 ) );
+    assert_eq!( loudness.get( &Position::new( 1, 0 ) ), Some( &2 ) );
+    assert_eq!( loudness.get( &Position::new( 2, 0 ) ), Some( &1 ) );
+    assert_eq!( loudness.get( &Position::new( 3, 0 ) ), None );
+  }
+  
+  #[test]
+  fn noise_spreads_diagonally() {
+    let map = Map::from_rows( &[ "   "
+                               , "   "
+                               , "   " ] );
+    let loudness = spread( &map, Position::new( 1, 1 ), 2 );
+    
+    assert_eq!( loudness.len(), 9 );
+    assert_eq!( loudness.get( &Position::new( 0, 0 ) ), Some( &1 ) );
+  }
+  
+  #[test]
+  fn walls_block_noise() {
+    let map = Map::from_rows( &[ "  #  "
+                               , "  #  " ] );
+    let loudness = spread( &map, Position::new( 0, 0 ), 10 );
+    
+    assert_eq!( loudness.get( &Position::new( 2, 0 ) ), None );
+    assert_eq!( loudness.get( &Position::new( 3, 0 ) ), None );
+  }
+  
+  #[test]
+  fn noise_goes_around_walls() {
+    let map = Map::from_rows( &[ " # "
+                               , "   " ] );
+    let loudness = spread( &map, Position::new( 0, 0 ), 3 );
+    
+    assert_eq!( loudness.get( &Position::new( 2, 0 ) ), Some( &1 ) );
+  }
+  
+  #[test]
+  fn silence_goes_nowhere() {
+    let map = Map::from_rows( &[ "   " ] );
+    
+    assert!( spread( &map, Position::new( 1, 0 ), 0 ).is_empty() );
+  }
+}
@@ -0,0 +1,30 @@
+use std::cell::RefCell;
+
+use util::*;
+
+/// The settings the player can change to suit how they like to play.
+#[derive(Clone, Copy, RustcDecodable)]
+pub struct Options {
+  /// Whether auto-explore picks up the items it walks over
+  pub auto_pickup : bool
+}
+
+thread_local!( static OPTIONS : RefCell<Options> =
+  RefCell::new( Options { auto_pickup: false } ) );
+
+/// Loads the options from `data/options.toml`.
+pub fn load_options() {
+  use toml::{decode, Value};
+  
+  let filename = "data/options.toml";
+  let data = load_data_file( filename );
+  
+  let options : Options = decode( Value::Table( data ) )
+    .expect( &format!( "Invalid options in '{}'", filename ) );
+  
+  OPTIONS.with( |o| *o.borrow_mut() = options );
+}
+
+pub fn options() -> Options {
+  OPTIONS.with( |o| *o.borrow() )
+}
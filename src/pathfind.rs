@@ -0,0 +1,266 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use map::{Map, NORMAL_MOVEMENT_COST};
+use util::*;
+
+// An open node in the search, ordered so the cheapest one comes first
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Node {
+  estimate : u32,
+  cost     : u32,
+  pos      : Position
+}
+
+impl Ord for Node {
+  fn cmp( &self, other : &Node ) -> Ordering {
+    match other.estimate.cmp( &self.estimate ) {
+      Ordering::Equal => other.cost.cmp( &self.cost ),
+      ord => ord
+    }
+  }
+}
+
+impl PartialOrd for Node {
+  fn partial_cmp( &self, other : &Node ) -> Option<Ordering> {
+    Some( self.cmp( other ) )
+  }
+}
+
+/// The positions next to `pos` that are inside the map.
+pub fn neighbours( map : &Map, pos : Position ) -> Vec<Position> {
+  DIRECTIONS.iter()
+    .filter_map( |dir| dir.try_offset_position( pos, map ) )
+    .collect()
+}
+
+/// Finds the quickest path from `from` to `to`, where `cost` gives the cost
+/// of entering each position as a percentage of a step, or `None` for
+/// positions that can't be entered. The path includes `to` but not `from`,
+/// and `to` is always considered enterable.
+pub fn find_path<F>( map : &Map, from : Position, to : Position, cost : F )
+  -> Option<Vec<Position>> where F : Fn( Position ) -> Option<u32> {
+  
+  let mut open = BinaryHeap::new();
+  let mut came_from : HashMap<Position, Position> = HashMap::new();
+  let mut best_cost : HashMap<Position, u32> = HashMap::new();
+  
+  // No step costs less than a normal one, which keeps the estimate from
+  // overshooting
+  let estimate = |pos : Position| pos.distance( to ) * NORMAL_MOVEMENT_COST;
+  
+  open.push( Node { estimate: estimate( from ), cost: 0, pos: from } );
+  best_cost.insert( from, 0 );
+  
+  while let Some( node ) = open.pop() {
+    if node.pos == to {
+      let mut path = vec![ to ];
+      let mut current = to;
+      
+      while let Some( &prev ) = came_from.get( &current ) {
+        if prev == from {
+          break;
+        }
+        
+        path.push( prev );
+        current = prev;
+      }
+      
+      path.reverse();
+      
+      return Some( path )
+    }
+    
+    if best_cost.get( &node.pos ).map( |&c| node.cost > c ).unwrap_or( false ) {
+      continue;
+    }
+    
+    for next in neighbours( map, node.pos ) {
+      let step = match cost( next ) {
+        Some( step ) => step,
+        None if next == to => NORMAL_MOVEMENT_COST,
+        None => continue
+      };
+      
+      let total = node.cost + step;
+      
+      if best_cost.get( &next ).map( |&c| total >= c ).unwrap_or( false ) {
+        continue;
+      }
+      
+      best_cost.insert( next, total );
+      came_from.insert( next, node.pos );
+      open.push( Node { estimate: total + estimate( next ), cost: total, pos: next } );
+    }
+  }
+  
+  None
+}
+
+/// The cost of getting from every position of a map to the closest of a set
+/// of goals, measured like the costs given to `find_path`.
+pub struct DistanceMap {
+  width     : usize,
+  distances : Vec<Option<u32>>
+}
+
+impl DistanceMap {
+  /// Computes the distances to `goals`, where `cost` gives the cost of
+  /// crossing each position on the way to them, or `None` if it can't be
+  /// crossed.
+  pub fn new<F>( map : &Map, goals : &[Position], cost : F ) -> DistanceMap
+    where F : Fn( Position ) -> Option<u32> {
+    
+    let mut distances : Vec<Option<u32>> = vec![ None; map.width * map.height ];
+    let mut open = BinaryHeap::new();
+    
+    for &goal in goals {
+      distances[ goal.x as usize + goal.y as usize * map.width ] = Some( 0 );
+      open.push( Node { estimate: 0, cost: 0, pos: goal } );
+    }
+    
+    while let Some( node ) = open.pop() {
+      let distance = distances[ node.pos.x as usize + node.pos.y as usize * map.width ]
+        .expect( "an open position has no distance" );
+      
+      // A cheaper way to the position was found after this one was queued
+      if node.cost > distance {
+        continue;
+      }
+      
+      for next in neighbours( map, node.pos ) {
+        let idx = next.x as usize + next.y as usize * map.width;
+        
+        let total = match cost( next ) {
+          Some( step ) => distance + step,
+          None => continue
+        };
+        
+        if distances[ idx ].map( |d| total >= d ).unwrap_or( false ) {
+          continue;
+        }
+        
+        distances[ idx ] = Some( total );
+        open.push( Node { estimate: total, cost: total, pos: next } );
+      }
+    }
+    
+    DistanceMap {
+      width:     map.width,
+      distances: distances
+    }
+  }
+  
+  /// The cost of getting from `pos` to the closest goal, or `None` if none
+  /// can be reached.
+  pub fn distance( &self, pos : Position ) -> Option<u32> {
+    self.distances[ pos.x as usize + pos.y as usize * self.width ]
+  }
+  
+  /// The position next to `pos` that's the closest to a goal.
+  pub fn next_step( &self, map : &Map, pos : Position ) -> Option<Position> {
+    let current = match self.distance( pos ) {
+      Some( d ) => d,
+      None => return None
+    };
+    
+    neighbours( map, pos ).into_iter()
+      .filter_map( |p| self.distance( p ).map( |d| (p, d) ) )
+      .filter( |&(_, d)| d < current )
+      .min_by_key( |&(_, d)| d )
+      .map( |(p, _)| p )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use map::Tile;
+  
+  // Walls can't be crossed, everything else takes a normal step
+  fn walking( map : &Map, pos : Position ) -> Option<u32> {
+    if map.tile_at( pos ).is_solid() { None } else { Some( NORMAL_MOVEMENT_COST ) }
+  }
+  
+  #[test]
+  fn straight_path() {
+    let map = Map::from_rows( &[ "     " ] );
+    let path = find_path( &map, Position::new( 0, 0 ), Position::new( 4, 0 )
+                        , |p| walking( &map, p ) );
+    
+    assert_eq!( path, Some( vec![ Position::new( 1, 0 ), Position::new( 2, 0 )
+                                , Position::new( 3, 0 ), Position::new( 4, 0 ) ] ) );
+  }
+  
+  #[test]
+  fn path_goes_around_walls() {
+    let map = Map::from_rows( &[ "  #  "
+                               , "  #  "
+                               , "     " ] );
+    let path = find_path( &map, Position::new( 0, 0 ), Position::new( 4, 0 )
+                        , |p| walking( &map, p ) )
+      .expect( "no path found" );
+    
+    assert_eq!( path.last(), Some( &Position::new( 4, 0 ) ) );
+    assert!( path.iter().all( |&p| !map.tile_at( p ).is_solid() ) );
+    assert!( path.contains( &Position::new( 2, 2 ) ) );
+  }
+  
+  #[test]
+  fn no_path_through_walls() {
+    let map = Map::from_rows( &[ "  #  "
+                               , "  #  " ] );
+    let path = find_path( &map, Position::new( 0, 0 ), Position::new( 4, 0 )
+                        , |p| walking( &map, p ) );
+    
+    assert_eq!( path, None );
+  }
+  
+  #[test]
+  fn target_is_always_enterable() {
+    let map = Map::from_rows( &[ "  #" ] );
+    let path = find_path( &map, Position::new( 0, 0 ), Position::new( 2, 0 )
+                        , |p| walking( &map, p ) );
+    
+    assert_eq!( path, Some( vec![ Position::new( 1, 0 ), Position::new( 2, 0 ) ] ) );
+  }
+  
+  #[test]
+  fn path_avoids_costly_tiles() {
+    // The shallow water on the direct route takes longer than going around
+    let map = Map::from_rows( &[ "     "
+                               , " ~~~ "
+                               , "     " ] );
+    let cost = |p : Position| {
+      Some( if map.tile_at( p ) == Tile::ShallowWater { 5 * NORMAL_MOVEMENT_COST }
+            else { NORMAL_MOVEMENT_COST } )
+    };
+    let path = find_path( &map, Position::new( 0, 1 ), Position::new( 4, 1 ), cost )
+      .expect( "no path found" );
+    
+    assert!( path.iter().all( |&p| map.tile_at( p ) != Tile::ShallowWater ) );
+  }
+  
+  #[test]
+  fn distance_map_counts_steps_to_the_closest_goal() {
+    let map = Map::from_rows( &[ "      "
+                               , " #### "
+                               , "      " ] );
+    let goals = [ Position::new( 0, 0 ), Position::new( 5, 2 ) ];
+    let distances = DistanceMap::new( &map, &goals, |p| walking( &map, p ) );
+    
+    assert_eq!( distances.distance( Position::new( 0, 0 ) ), Some( 0 ) );
+    assert_eq!( distances.distance( Position::new( 2, 0 ) ), Some( 2 * NORMAL_MOVEMENT_COST ) );
+    assert_eq!( distances.distance( Position::new( 4, 2 ) ), Some( NORMAL_MOVEMENT_COST ) );
+    assert_eq!( distances.distance( Position::new( 1, 1 ) ), None );
+  }
+  
+  #[test]
+  fn distance_map_steps_towards_goals() {
+    let map = Map::from_rows( &[ "    " ] );
+    let distances = DistanceMap::new( &map, &[ Position::new( 0, 0 ) ], |p| walking( &map, p ) );
+    
+    assert_eq!( distances.next_step( &map, Position::new( 3, 0 ) ), Some( Position::new( 2, 0 ) ) );
+    assert_eq!( distances.next_step( &map, Position::new( 0, 0 ) ), None );
+  }
+}
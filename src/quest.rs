@@ -0,0 +1,250 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use util::*;
+use description::*;
+
+/// How far the player has come with a quest.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuestStatus {
+  NotStarted,
+  Active,
+  Completed
+}
+
+impl QuestStatus {
+  pub fn from_config( name : &str ) -> Option<QuestStatus> {
+    Some( match name {
+      "not_started" => QuestStatus::NotStarted,
+      "active"      => QuestStatus::Active,
+      "completed"   => QuestStatus::Completed,
+      _             => return None
+    } )
+  }
+}
+
+/// Something the player has to do for a quest.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Objective {
+  /// Kill the given number of monsters of a kind
+  Kill( String, u32 ),
+  /// Carry an item of the given kind
+  Fetch( String ),
+  /// Get down to the given depth
+  ReachDepth( u32 )
+}
+
+impl Objective {
+  /// The progress it takes to complete the objective.
+  pub fn target( &self ) -> u32 {
+    match *self {
+      Objective::Kill( _, count ) => count,
+      _ => 1
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct QuestObjective {
+  /// What the journal says has to be done
+  pub text : String,
+  pub goal : Objective
+}
+
+/// A quest as defined in `data/quest.toml`.
+#[derive(Clone, Debug)]
+pub struct Quest {
+  desc_id        : String,
+  /// Starts the quest once met, for quests that aren't handed out in
+  /// conversations
+  pub trigger    : Option<Objective>,
+  pub objectives : Vec<QuestObjective>,
+  /// The kinds of items handed to the player when the quest is completed
+  pub reward     : Vec<String>
+}
+
+impl Describe for Quest {
+  fn desc_id( &self ) -> String {
+    self.desc_id.clone()
+  }
+}
+
+/// Identifies a quest, as loaded from the data file.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct QuestId( usize );
+
+#[derive(RustcDecodable)]
+struct ObjectiveConfig {
+  text  : Option<String>,
+  kill  : Option<String>,
+  count : Option<u32>,
+  fetch : Option<String>,
+  depth : Option<u32>
+}
+
+impl ObjectiveConfig {
+  fn goal( &self ) -> Option<Objective> {
+    Some( if let Some( ref kind ) = self.kill {
+      Objective::Kill( kind.clone(), self.count.unwrap_or( 1 ) )
+    } else if let Some( ref kind ) = self.fetch {
+      Objective::Fetch( kind.clone() )
+    } else if let Some( depth ) = self.depth {
+      Objective::ReachDepth( depth )
+    } else {
+      return None
+    } )
+  }
+}
+
+#[derive(RustcDecodable)]
+struct QuestConfig {
+  trigger    : Option<ObjectiveConfig>,
+  objectives : Vec<ObjectiveConfig>,
+  reward     : Option<Vec<String>>
+}
+
+thread_local!( static QUESTS : RefCell<Vec<(String, Quest)>> = RefCell::new( Vec::new() ) );
+
+/// Loads the quests from `data/quest.toml`. Their names and descriptions are
+/// loaded from the same file along with the other descriptions.
+pub fn load_quests() {
+  use toml::decode;
+  
+  let filename = "data/quest.toml";
+  let data = load_data_file( filename );
+  
+  QUESTS.with( |quests| {
+    let mut q = quests.borrow_mut();
+    
+    for (entry_name, entry_value) in data.into_iter() {
+      let config : QuestConfig = decode( entry_value )
+        .expect( &format!( "Invalid quest `{}` in '{}'", entry_name, filename ) );
+      
+      let trigger = config.trigger.map( |t| {
+        t.goal().expect( &format!( "Invalid trigger for quest `{}`", entry_name ) )
+      } );
+      
+      let objectives = config.objectives.into_iter()
+        .map( |o| {
+          let goal = o.goal()
+            .expect( &format!( "Invalid objective for quest `{}`", entry_name ) );
+          let text = o.text
+            .expect( &format!( "An objective of quest `{}` has no text", entry_name ) );
+          
+          QuestObjective { text: text, goal: goal }
+        } )
+        .collect();
+      
+      let quest = Quest {
+        desc_id: format!( "quest.{}", entry_name ),
+        trigger: trigger,
+        objectives: objectives,
+        reward: config.reward.unwrap_or( Vec::new() )
+      };
+      
+      q.push( (entry_name, quest) );
+    }
+  } );
+}
+
+/// Looks up a quest by its name in the data file.
+pub fn quest_id( name : &str ) -> Option<QuestId> {
+  QUESTS.with( |quests| {
+    quests.borrow().iter().position( |&(ref n, _)| n == name ).map( QuestId )
+  } )
+}
+
+pub fn quest( id : QuestId ) -> Quest {
+  QUESTS.with( |quests| quests.borrow()[ id.0 ].1.clone() )
+}
+
+/// All the quests there are, in the order they were loaded.
+pub fn quest_ids() -> Vec<QuestId> {
+  QUESTS.with( |quests| (0..quests.borrow().len()).map( QuestId ).collect() )
+}
+
+// A quest the player has taken on
+struct JournalEntry {
+  status   : QuestStatus,
+  // How far along each of the objectives is
+  progress : Vec<u32>
+}
+
+/// Keeps track of the quests the player has taken on and how far along
+/// their objectives are.
+pub struct Journal {
+  entries : HashMap<QuestId, JournalEntry>
+}
+
+impl Journal {
+  pub fn new() -> Journal {
+    Journal {
+      entries: HashMap::new()
+    }
+  }
+  
+  pub fn status( &self, id : QuestId ) -> QuestStatus {
+    self.entries.get( &id ).map( |e| e.status ).unwrap_or( QuestStatus::NotStarted )
+  }
+  
+  /// The quests that have been started, in the order they were loaded.
+  pub fn started( &self ) -> Vec<QuestId> {
+    quest_ids().into_iter().filter( |id| self.entries.contains_key( id ) ).collect()
+  }
+  
+  /// Takes on the quest, returning whether it hadn't been started before.
+  pub fn start( &mut self, id : QuestId ) -> bool {
+    if self.status( id ) != QuestStatus::NotStarted {
+      return false
+    }
+    
+    let objectives = quest( id ).objectives.len();
+    
+    self.entries.insert( id, JournalEntry {
+      status: QuestStatus::Active,
+      progress: vec![ 0; objectives ]
+    } );
+    
+    true
+  }
+  
+  pub fn progress( &self, id : QuestId, objective : usize ) -> u32 {
+    self.entries.get( &id ).map( |e| e.progress[ objective ] ).unwrap_or( 0 )
+  }
+  
+  /// Moves an objective of an active quest along, returning whether that
+  /// completed it.
+  pub fn advance( &mut self, id : QuestId, objective : usize, amount : u32 ) -> bool {
+    let target = quest( id ).objectives[ objective ].goal.target();
+    
+    match self.entries.get_mut( &id ) {
+      Some( entry ) if entry.status == QuestStatus::Active => {
+        let before = entry.progress[ objective ];
+        entry.progress[ objective ] = ::std::cmp::min( before + amount, target );
+        
+        before < target && entry.progress[ objective ] == target
+      },
+      _ => false
+    }
+  }
+  
+  /// Marks the quest as completed if all of its objectives are, returning
+  /// whether it was.
+  pub fn complete_if_done( &mut self, id : QuestId ) -> bool {
+    let quest = quest( id );
+    
+    match self.entries.get_mut( &id ) {
+      Some( entry ) if entry.status == QuestStatus::Active => {
+        let done = quest.objectives.iter().zip( entry.progress.iter() )
+          .all( |(objective, &progress)| progress >= objective.goal.target() );
+        
+        if done {
+          entry.status = QuestStatus::Completed;
+        }
+        
+        done
+      },
+      _ => false
+    }
+  }
+}
@@ -0,0 +1,33 @@
+use util::*;
+use entity::Entity;
+
+/// The faction a shopkeeper joins once someone makes off with its goods.
+pub const ANGRY_SHOPKEEPER_FACTION : &'static str = "angry_shopkeepers";
+
+/// The gold an actor carries, or that lies on the ground as a pile.
+pub struct Purse {
+  pub gold : u32
+}
+
+/// What an item costs in a shop, in gold.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Price( pub u32 );
+
+impl Price {
+  /// What a shopkeeper pays for an item of this price. Shops don't buy
+  /// anything for what they sell it for.
+  pub fn selling( self ) -> u32 {
+    self.0 / 2
+  }
+}
+
+/// Marks an item as belonging to a shop until it's paid for.
+pub struct ForSale {
+  pub keeper : Entity
+}
+
+/// Keeps a shop in `area`, selling the items in it and buying what's
+/// brought in.
+pub struct Shopkeeper {
+  pub area : Area
+}
@@ -0,0 +1,243 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use description::*;
+use util::*;
+
+use self::SkillKind::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SkillKind {
+  Melee,
+  Ranged,
+  Stealth,
+  Searching,
+  Magic
+}
+
+/// Every skill, in the order they're listed on the character sheet.
+pub const SKILL_KINDS : [SkillKind; 5] = [ Melee, Ranged, Stealth, Searching, Magic ];
+
+impl SkillKind {
+  pub fn from_config( name : &str ) -> Option<SkillKind> {
+    Some( match name {
+      "melee"     => Melee,
+      "ranged"    => Ranged,
+      "stealth"   => Stealth,
+      "searching" => Searching,
+      "magic"     => Magic,
+      _           => return None
+    } )
+  }
+  
+  fn config_name( self ) -> &'static str {
+    match self {
+      Melee     => "melee",
+      Ranged    => "ranged",
+      Stealth   => "stealth",
+      Searching => "searching",
+      Magic     => "magic"
+    }
+  }
+}
+
+impl Describe for SkillKind {
+  fn desc_id( &self ) -> String {
+    format!( "skill.{}", self.config_name() )
+  }
+}
+
+/// How a skill works, as configured in `data/skill.toml`.
+#[derive(Clone, Copy, RustcDecodable)]
+struct SkillDefinition {
+  // The chance in percent of the skill's action succeeding at level 0
+  base      : i32,
+  // How much the chance changes with each level
+  per_level : i32,
+  // The number of uses it takes to go from level 0 to 1. Every level after
+  // that takes as many uses more.
+  practice  : u32,
+  max_level : u32
+}
+
+thread_local!( static SKILLS : RefCell<HashMap<SkillKind, SkillDefinition>> =
+  RefCell::new( HashMap::new() ) );
+
+/// Loads the skill definitions from `data/skill.toml`. Their names and
+/// descriptions are loaded from the same file along with the other
+/// descriptions.
+pub fn load_skills() {
+  use toml::decode;
+  
+  let filename = "data/skill.toml";
+  let data = load_data_file( filename );
+  
+  SKILLS.with( |skills| {
+    let mut s = skills.borrow_mut();
+    
+    for (entry_name, entry_value) in data.into_iter() {
+      let kind = SkillKind::from_config( &entry_name )
+        .expect( &format!( "Unknown skill `{}` in '{}'", entry_name, filename ) );
+      let definition : SkillDefinition = decode( entry_value )
+        .expect( &format!( "Invalid skill `{}` in '{}'", entry_name, filename ) );
+      
+      s.insert( kind, definition );
+    }
+    
+    for &kind in SKILL_KINDS.iter() {
+      assert!( s.contains_key( &kind ), "The skill `{}` is missing from '{}'"
+             , kind.config_name(), filename );
+    }
+  } );
+}
+
+fn definition( kind : SkillKind ) -> SkillDefinition {
+  SKILLS.with( |skills| {
+    *skills.borrow().get( &kind )
+      .expect( &format!( "No skill `{}` was found in the skill definitions."
+                       , kind.config_name() ) )
+  } )
+}
+
+#[derive(Clone, Copy)]
+struct Skill {
+  level    : u32,
+  // The uses since the last level was gained
+  practice : u32
+}
+
+/// The skills of an actor. Skills get better the more they're used.
+pub struct Skills {
+  skills : HashMap<SkillKind, Skill>
+}
+
+impl Skills {
+  pub fn new() -> Skills {
+    Skills {
+      skills: HashMap::new()
+    }
+  }
+  
+  /// Starts out with the given levels, keyed by the skills' names in the
+  /// data files.
+  pub fn from_config( levels : &HashMap<String, u32> ) -> Skills {
+    let mut skills = Skills::new();
+    
+    for (name, &level) in levels {
+      let kind = SkillKind::from_config( name )
+        .expect( &format!( "Unknown skill `{}`", name ) );
+      
+      skills.raise( kind, level );
+    }
+    
+    skills
+  }
+  
+  pub fn level( &self, kind : SkillKind ) -> u32 {
+    self.skills.get( &kind ).map( |s| s.level ).unwrap_or( 0 )
+  }
+  
+  /// Raises the skill by the given number of levels, up to its maximum.
+  pub fn raise( &mut self, kind : SkillKind, levels : u32 ) {
+    let max_level = definition( kind ).max_level;
+    let skill = self.skills.entry( kind ).or_insert( Skill { level: 0, practice: 0 } );
+    
+    skill.level = ::std::cmp::min( skill.level + levels, max_level );
+  }
+  
+  /// The chance in percent of the skill's action succeeding.
+  pub fn chance( &self, kind : SkillKind ) -> u32 {
+    let def = definition( kind );
+    let chance = def.base + def.per_level * self.level( kind ) as i32;
+    
+    if chance < 0 { 0 } else if chance > 100 { 100 } else { chance as u32 }
+  }
+  
+  /// Counts a use of the skill, returning the new level if it improved.
+  pub fn practice( &mut self, kind : SkillKind ) -> Option<u32> {
+    let def = definition( kind );
+    let skill = self.skills.entry( kind ).or_insert( Skill { level: 0, practice: 0 } );
+    
+    if skill.level >= def.max_level {
+      return None
+    }
+    
+    skill.practice += 1;
+    
+    if skill.practice < def.practice * ( skill.level + 1 ) {
+      return None
+    }
+    
+    skill.level += 1;
+    skill.practice = 0;
+    
+    Some( skill.level )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  
+  fn define_melee( practice : u32, max_level : u32 ) {
+    SKILLS.with( |skills| {
+      skills.borrow_mut().insert( Melee, SkillDefinition { base: 50, per_level: 10
+                                                         , practice: practice
+                                                         , max_level: max_level } );
+    } );
+  }
+  
+  #[test]
+  fn practice_gains_a_level_after_enough_uses() {
+    define_melee( 3, 5 );
+    let mut skills = Skills::new();
+    
+    assert_eq!( skills.practice( Melee ), None );
+    assert_eq!( skills.practice( Melee ), None );
+    assert_eq!( skills.practice( Melee ), Some( 1 ) );
+    assert_eq!( skills.level( Melee ), 1 );
+  }
+  
+  #[test]
+  fn each_level_takes_more_practice() {
+    define_melee( 3, 5 );
+    let mut skills = Skills::new();
+    skills.raise( Melee, 1 );
+    
+    for _ in 0..5 {
+      assert_eq!( skills.practice( Melee ), None );
+    }
+    
+    assert_eq!( skills.practice( Melee ), Some( 2 ) );
+  }
+  
+  #[test]
+  fn practice_stops_at_the_maximum_level() {
+    define_melee( 1, 2 );
+    let mut skills = Skills::new();
+    
+    assert_eq!( skills.practice( Melee ), Some( 1 ) );
+    assert_eq!( skills.practice( Melee ), None );
+    assert_eq!( skills.practice( Melee ), Some( 2 ) );
+    
+    for _ in 0..10 {
+      assert_eq!( skills.practice( Melee ), None );
+    }
+    
+    assert_eq!( skills.level( Melee ), 2 );
+  }
+  
+  #[test]
+  fn chance_grows_with_the_level() {
+    define_melee( 1, 10 );
+    let mut skills = Skills::new();
+    
+    assert_eq!( skills.chance( Melee ), 50 );
+    
+    skills.raise( Melee, 2 );
+    assert_eq!( skills.chance( Melee ), 70 );
+    
+    skills.raise( Melee, 8 );
+    assert_eq!( skills.chance( Melee ), 100 );
+  }
+}
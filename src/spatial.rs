@@ -0,0 +1,97 @@
+use std::cmp::min;
+
+use util::*;
+use entity::Entity;
+
+/// Keeps track of which actor occupies each tile of the map, so actors can
+/// be looked up by position without going through every entity in the world.
+pub struct Occupancy {
+  width  : usize,
+  height : usize,
+  cells  : Vec<Option<Entity>>
+}
+
+impl Occupancy {
+  pub fn new( width : usize, height : usize ) -> Occupancy {
+    Occupancy {
+      width:  width,
+      height: height,
+      cells:  vec![ None; width * height ]
+    }
+  }
+  
+  fn index( &self, pos : Position ) -> usize {
+    let x = pos.x as usize;
+    let y = pos.y as usize;
+    
+    assert!( x < self.width && y < self.height
+           , "the given position is outside the map bounds" );
+    
+    x + y * self.width
+  }
+  
+  pub fn at( &self, pos : Position ) -> Option<Entity> {
+    self.cells[ self.index( pos ) ]
+  }
+  
+  pub fn is_occupied( &self, pos : Position ) -> bool {
+    self.at( pos ).is_some()
+  }
+  
+  pub fn insert( &mut self, pos : Position, id : Entity ) {
+    let idx = self.index( pos );
+    
+    assert!( self.cells[ idx ].is_none(), "the given position is already occupied" );
+    
+    self.cells[ idx ] = Some( id );
+  }
+  
+  /// Removes the given actor from `pos`, if it's the one occupying it.
+  pub fn remove( &mut self, pos : Position, id : Entity ) {
+    let idx = self.index( pos );
+    
+    if self.cells[ idx ] == Some( id ) {
+      self.cells[ idx ] = None;
+    }
+  }
+  
+  pub fn move_actor( &mut self, from : Position, to : Position, id : Entity ) {
+    self.remove( from, id );
+    self.insert( to, id );
+  }
+  
+  /// Returns the actors within `radius` tiles of `pos`, counting diagonal
+  /// steps as a single tile, along with their positions.
+  pub fn within_radius( &self, pos : Position, radius : u32 )
+    -> Vec<(Entity, Position)> {
+    
+    let r = radius as usize;
+    let (x, y) = (pos.x as usize, pos.y as usize);
+    
+    let (x0, y0) = (x.saturating_sub( r ), y.saturating_sub( r ));
+    let (x1, y1) = (min( x + r, self.width - 1 ), min( y + r, self.height - 1 ));
+    
+    let mut found = Vec::new();
+    
+    for cy in y0..y1 + 1 {
+      for cx in x0..x1 + 1 {
+        if let Some( id ) = self.cells[ cx + cy * self.width ] {
+          found.push( (id, Position::new( cx as u32, cy as u32 )) );
+        }
+      }
+    }
+    
+    found
+  }
+  
+  /// Finds the closest actor to `pos` within `radius` for which `pred`
+  /// holds.
+  pub fn nearest<F>( &self, pos : Position, radius : u32, pred : F )
+    -> Option<Entity> where F : Fn( Entity ) -> bool {
+    
+    self.within_radius( pos, radius ).into_iter()
+      .filter( |&(id, _)| pred( id ) )
+      .min_by_key( |&(_, p)| pos.distance( p ) )
+      .map( |(id, _)| id )
+  }
+}
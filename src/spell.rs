@@ -0,0 +1,225 @@
+use std::cell::RefCell;
+
+use util::*;
+use description::*;
+use status::StatusKind;
+
+/// How a spell picks what it affects.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SpellTargeting {
+  /// Affects the caster
+  OnSelf,
+  /// Flies towards a position, affecting the first actor in its way
+  Bolt,
+  /// Affects the chosen position directly
+  Position
+}
+
+impl SpellTargeting {
+  fn from_config( name : &str ) -> Option<SpellTargeting> {
+    Some( match name {
+      "self"     => SpellTargeting::OnSelf,
+      "bolt"     => SpellTargeting::Bolt,
+      "position" => SpellTargeting::Position,
+      _          => return None
+    } )
+  }
+}
+
+/// What a spell does to its target.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SpellEffect {
+  Damage( u32 ),
+  Heal( u32 ),
+  /// Applies the status effect for the given duration
+  Status( StatusKind, u32 ),
+  /// Moves the caster to the target position
+  Blink,
+  /// Calls up a monster of the given kind next to the target position
+  Summon( String )
+}
+
+/// A spell as defined in `data/spell.toml`.
+#[derive(Clone, Debug)]
+pub struct Spell {
+  desc_id          : String,
+  /// The mana it takes to cast the spell
+  pub cost         : u32,
+  /// The time it takes to cast the spell, used as the duration of the action
+  pub casting_time : u32,
+  pub range        : u32,
+  pub targeting    : SpellTargeting,
+  pub effect       : SpellEffect
+}
+
+impl Describe for Spell {
+  fn desc_id( &self ) -> String {
+    self.desc_id.clone()
+  }
+}
+
+/// Identifies a spell, as loaded from the data file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SpellId( usize );
+
+#[derive(RustcDecodable)]
+struct SpellConfig {
+  cost         : u32,
+  casting_time : u32,
+  range        : Option<u32>,
+  targeting    : String,
+  effect       : String,
+  amount       : Option<u32>,
+  status       : Option<String>,
+  duration     : Option<u32>,
+  monster      : Option<String>
+}
+
+impl SpellConfig {
+  fn effect( &self ) -> Option<SpellEffect> {
+    Some( match &self.effect[..] {
+      "damage" => SpellEffect::Damage( self.amount.unwrap_or( 0 ) ),
+      "heal" => SpellEffect::Heal( self.amount.unwrap_or( 0 ) ),
+      "status" => match self.status.as_ref().and_then( |s| StatusKind::from_config( s ) ) {
+        Some( kind ) => SpellEffect::Status( kind, self.duration.unwrap_or( 0 ) ),
+        None => return None
+      },
+      "blink" => SpellEffect::Blink,
+      "summon" => match self.monster {
+        Some( ref kind ) => SpellEffect::Summon( kind.clone() ),
+        None => return None
+      },
+      _ => return None
+    } )
+  }
+}
+
+thread_local!( static SPELLS : RefCell<Vec<(String, Spell)>> = RefCell::new( Vec::new() ) );
+
+/// Loads the spells from `data/spell.toml`. Their names and descriptions are
+/// loaded from the same file along with the other descriptions.
+pub fn load_spells() {
+  use toml::decode;
+  
+  let filename = "data/spell.toml";
+  let data = load_data_file( filename );
+  
+  SPELLS.with( |spells| {
+    let mut s = spells.borrow_mut();
+    
+    for (entry_name, entry_value) in data.into_iter() {
+      let config : SpellConfig = decode( entry_value )
+        .expect( &format!( "Invalid spell `{}` in '{}'", entry_name, filename ) );
+      
+      let targeting = SpellTargeting::from_config( &config.targeting )
+        .expect( &format!( "Invalid targeting `{}` for spell `{}`", config.targeting, entry_name ) );
+      let effect = config.effect()
+        .expect( &format!( "Invalid effect `{}` for spell `{}`", config.effect, entry_name ) );
+      
+      let spell = Spell {
+        desc_id: format!( "spell.{}", entry_name ),
+        cost: config.cost,
+        casting_time: config.casting_time,
+        range: config.range.unwrap_or( 0 ),
+        targeting: targeting,
+        effect: effect
+      };
+      
+      s.push( (entry_name, spell) );
+    }
+  } );
+}
+
+/// Looks up a spell by its name in the data file.
+pub fn spell_id( name : &str ) -> Option<SpellId> {
+  SPELLS.with( |spells| {
+    spells.borrow().iter().position( |&(ref n, _)| n == name ).map( SpellId )
+  } )
+}
+
+pub fn spell( id : SpellId ) -> Spell {
+  SPELLS.with( |spells| spells.borrow()[ id.0 ].1.clone() )
+}
+
+/// The amount of time it takes to regain a point of mana, measured in the
+/// same units as `Action::duration`.
+pub const MANA_TICK : u32 = 200;
+
+/// The magical energy an actor casts spells with. It comes back slowly as
+/// time passes.
+pub struct Mana {
+  pub current : u32,
+  pub max     : u32,
+  // Time passed since the last point was regained
+  elapsed     : u32
+}
+
+impl Mana {
+  pub fn new( max : u32 ) -> Mana {
+    Mana {
+      current: max,
+      max: max,
+      elapsed: 0
+    }
+  }
+  
+  pub fn regenerate( &mut self, duration : u32 ) {
+    self.elapsed += duration;
+    
+    let points = self.elapsed / MANA_TICK;
+    self.elapsed %= MANA_TICK;
+    
+    self.current = ::std::cmp::min( self.current + points, self.max );
+  }
+}
+
+/// The spells an actor knows.
+pub struct Spellbook {
+  pub spells : Vec<SpellId>
+}
+
+impl Spellbook {
+  /// Learns the spells with the given names.
+  pub fn from_config( names : &[String] ) -> Spellbook {
+    Spellbook {
+      spells: names.iter()
+        .map( |name| spell_id( name ).expect( &format!( "Unknown spell `{}`", name ) ) )
+        .collect()
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  
+  #[test]
+  fn mana_comes_back_a_point_per_tick() {
+    let mut mana = Mana::new( 10 );
+    mana.current = 2;
+    
+    mana.regenerate( MANA_TICK * 3 );
+    assert_eq!( mana.current, 5 );
+  }
+  
+  #[test]
+  fn partial_ticks_add_up() {
+    let mut mana = Mana::new( 10 );
+    mana.current = 0;
+    
+    mana.regenerate( MANA_TICK / 2 );
+    assert_eq!( mana.current, 0 );
+    
+    mana.regenerate( MANA_TICK / 2 );
+    assert_eq!( mana.current, 1 );
+  }
+  
+  #[test]
+  fn mana_stops_at_the_maximum() {
+    let mut mana = Mana::new( 10 );
+    mana.current = 9;
+    
+    mana.regenerate( MANA_TICK * 5 );
+    assert_eq!( mana.current, 10 );
+  }
+}
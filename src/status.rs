@@ -0,0 +1,238 @@
+use description::*;
+use actor::Stats;
+
+use self::StatusKind::*;
+
+/// The amount of time that has to pass between each time a status effect
+/// applies its periodic effect, measured in the same units as
+/// `Action::duration`.
+pub const STATUS_TICK : u32 = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StatusKind {
+  Poison,
+  Regeneration,
+  Haste,
+  Slow,
+  Confusion,
+  Blindness
+}
+
+impl StatusKind {
+  pub fn from_config( name : &str ) -> Option<StatusKind> {
+    Some( match name {
+      "poison"       => Poison,
+      "regeneration" => Regeneration,
+      "haste"        => Haste,
+      "slow"         => Slow,
+      "confusion"    => Confusion,
+      "blindness"    => Blindness,
+      _              => return None
+    } )
+  }
+}
+
+impl Describe for StatusKind {
+  fn desc_id( &self ) -> String {
+    match *self {
+      Poison => "status.poison",
+      Regeneration => "status.regeneration",
+      Haste => "status.haste",
+      Slow => "status.slow",
+      Confusion => "status.confusion",
+      Blindness => "status.blindness"
+    }.to_string()
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct StatusEffect {
+  pub kind      : StatusKind,
+  pub remaining : u32,
+  // Time passed since the last tick of the effect
+  elapsed       : u32
+}
+
+impl StatusEffect {
+  pub fn new( kind : StatusKind, duration : u32 ) -> StatusEffect {
+    StatusEffect {
+      kind: kind,
+      remaining: duration,
+      elapsed: 0
+    }
+  }
+  
+  // Advances the effect by `duration`, returning the number of whole ticks
+  // that passed while it was still active.
+  fn advance( &mut self, duration : u32 ) -> u32 {
+    let passed = if duration < self.remaining { duration } else { self.remaining };
+    
+    self.remaining -= passed;
+    self.elapsed += passed;
+    
+    let ticks = self.elapsed / STATUS_TICK;
+    self.elapsed %= STATUS_TICK;
+    
+    ticks
+  }
+  
+  fn tick( &self, stats : &mut Stats ) {
+    match self.kind {
+      // Poison weakens, but never kills by itself
+      Poison => if stats.health > 1 {
+        stats.health -= 1;
+      },
+      Regeneration => if stats.health < stats.max_health {
+        stats.health += 1;
+      },
+      _ => {}
+    }
+  }
+}
+
+pub struct StatusEffects {
+  effects : Vec<StatusEffect>
+}
+
+impl StatusEffects {
+  pub fn new() -> StatusEffects {
+    StatusEffects {
+      effects: Vec::new()
+    }
+  }
+  
+  /// Applies a status effect for the given duration. If the effect is already
+  /// active its remaining duration is extended to `duration` if that's longer.
+  pub fn apply( &mut self, kind : StatusKind, duration : u32 ) {
+    if let Some( effect ) = self.effects.iter_mut().find( |e| e.kind == kind ) {
+      if effect.remaining < duration {
+        effect.remaining = duration;
+      }
+      
+      return
+    }
+    
+    self.effects.push( StatusEffect::new( kind, duration ) );
+  }
+  
+  pub fn has( &self, kind : StatusKind ) -> bool {
+    self.effects.iter().any( |e| e.kind == kind )
+  }
+  
+  pub fn iter( &self ) -> ::std::slice::Iter<StatusEffect> {
+    self.effects.iter()
+  }
+  
+  /// Modifies the duration of a movement made at the given speed. Haste
+  /// halves the time it takes, while slow doubles it.
+  pub fn modify_speed( &self, speed : u32 ) -> u32 {
+    let mut speed = speed;
+    
+    if self.has( Haste ) {
+      speed /= 2;
+    }
+    
+    if self.has( Slow ) {
+      speed *= 2;
+    }
+    
+    if speed == 0 { 1 } else { speed }
+  }
+  
+  pub fn update( &mut self, duration : u32, stats : &mut Stats ) {
+    for effect in self.effects.iter_mut() {
+      for _ in 0..effect.advance( duration ) {
+        effect.tick( stats );
+      }
+    }
+    
+    self.effects.retain( |e| e.remaining != 0 );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  
+  fn stats( health : u32 ) -> Stats {
+    Stats { health: health, max_health: 10, speed: 100, attack: 1, defense: 1 }
+  }
+  
+  #[test]
+  fn poison_ticks_once_per_tick() {
+    let mut effects = StatusEffects::new();
+    let mut stats = stats( 10 );
+    
+    effects.apply( StatusKind::Poison, 500 );
+    effects.update( STATUS_TICK * 2 + STATUS_TICK / 2, &mut stats );
+    assert_eq!( stats.health, 8 );
+    
+    // The half tick left over counts towards the next one
+    effects.update( STATUS_TICK / 2, &mut stats );
+    assert_eq!( stats.health, 7 );
+  }
+  
+  #[test]
+  fn poison_never_kills() {
+    let mut effects = StatusEffects::new();
+    let mut stats = stats( 2 );
+    
+    effects.apply( StatusKind::Poison, STATUS_TICK * 5 );
+    effects.update( STATUS_TICK * 5, &mut stats );
+    
+    assert_eq!( stats.health, 1 );
+  }
+  
+  #[test]
+  fn regeneration_stops_at_max_health() {
+    let mut effects = StatusEffects::new();
+    let mut stats = stats( 9 );
+    
+    effects.apply( StatusKind::Regeneration, STATUS_TICK * 3 );
+    effects.update( STATUS_TICK * 3, &mut stats );
+    
+    assert_eq!( stats.health, 10 );
+  }
+  
+  #[test]
+  fn effects_wear_off() {
+    let mut effects = StatusEffects::new();
+    let mut stats = stats( 10 );
+    
+    effects.apply( StatusKind::Haste, 150 );
+    effects.update( 100, &mut stats );
+    assert!( effects.has( StatusKind::Haste ) );
+    
+    // Only the time the effect had left can tick
+    effects.apply( StatusKind::Poison, 50 );
+    effects.update( 1000, &mut stats );
+    assert!( !effects.has( StatusKind::Haste ) );
+    assert!( !effects.has( StatusKind::Poison ) );
+    assert_eq!( stats.health, 10 );
+  }
+  
+  #[test]
+  fn applying_again_keeps_the_longer_duration() {
+    let mut effects = StatusEffects::new();
+    
+    effects.apply( StatusKind::Slow, 300 );
+    effects.apply( StatusKind::Slow, 100 );
+    assert_eq!( effects.iter().next().map( |e| e.remaining ), Some( 300 ) );
+    
+    effects.apply( StatusKind::Slow, 400 );
+    assert_eq!( effects.iter().count(), 1 );
+    assert_eq!( effects.iter().next().map( |e| e.remaining ), Some( 400 ) );
+  }
+  
+  #[test]
+  fn speed_modifiers() {
+    let mut effects = StatusEffects::new();
+    assert_eq!( effects.modify_speed( 100 ), 100 );
+    
+    effects.apply( StatusKind::Haste, 100 );
+    assert_eq!( effects.modify_speed( 100 ), 50 );
+    
+    effects.apply( StatusKind::Slow, 100 );
+    assert_eq!( effects.modify_speed( 100 ), 100 );
+  }
+}
@@ -0,0 +1,167 @@
+use ::tcod::Console;
+use ::tcod::console::BackgroundFlag;
+use ::tcod::colors;
+
+use util::*;
+use entity::Entity;
+use world::World;
+
+/// A cursor for picking a position to aim at. It starts on the closest
+/// hostile the player can see, can jump between the visible hostiles in
+/// range, and can be moved freely.
+pub struct Targeting {
+  origin     : Position,
+  range      : u32,
+  pub cursor : Position,
+  // The visible hostiles within range, closest first
+  targets    : Vec<Entity>,
+  current    : usize
+}
+
+impl Targeting {
+  /// Starts aiming from `origin` at things up to `range` tiles away.
+  pub fn new( world : &World, origin : Position, range : u32 ) -> Targeting {
+    let player = world.player();
+    
+    let mut targets : Vec<(Entity, Position)> = world.actors_within( origin, range ).into_iter()
+      .filter( |&e| world.are_hostile( player, e ) )
+      .filter_map( |e| world.entities.position( e ).map( |p| (e, p) ) )
+      .filter( |&(_, p)| world.can_see( p ) )
+      .collect();
+    
+    targets.sort_by_key( |&(_, p)| origin.distance( p ) );
+    
+    Targeting {
+      origin: origin,
+      range: range,
+      cursor: targets.first().map( |&(_, p)| p ).unwrap_or( origin ),
+      targets: targets.into_iter().map( |(e, _)| e ).collect(),
+      current: 0
+    }
+  }
+  
+  /// Moves the cursor onto the next hostile in range.
+  pub fn cycle( &mut self, world : &World ) {
+    if self.targets.is_empty() {
+      return
+    }
+    
+    self.current = (self.current + 1) % self.targets.len();
+    
+    if let Some( pos ) = world.entities.position( self.targets[ self.current ] ) {
+      self.cursor = pos;
+    }
+  }
+  
+  pub fn move_cursor( &mut self, world : &World, dir : Direction ) {
+    if let Some( pos ) = dir.try_offset_position( self.cursor, &world.map ) {
+      self.cursor = pos;
+    }
+  }
+  
+  /// Highlights the path a projectile would take towards the cursor, and
+  /// the cursor itself.
+  pub fn render<C : Console>( &self, ctx : &mut C, world : &World ) {
+    for pos in world.projectile_path( self.origin, self.cursor, self.range ) {
+      ctx.set_char_background( pos.x as i32, pos.y as i32
+                             , colors::DARK_SEPIA, BackgroundFlag::Set );
+    }
+    
+    ctx.set_char_background( self.cursor.x as i32, self.cursor.y as i32
+                           , colors::YELLOW, BackgroundFlag::Set );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use map::Tile;
+  use world::tests::{test_world, place};
+  
+  const DOOR : Position = Position { x: 5, y: 6 };
+  
+  // A world with the player standing in the open door to the room below
+  // the starting one, looking at the monsters in it
+  fn world_at_the_door() -> World {
+    let mut world = test_world();
+    let player = world.player();
+    
+    world.map.set_tile( DOOR, Tile::OpenDoor );
+    place( &mut world, player, DOOR );
+    world.update_lighting();
+    
+    world
+  }
+  
+  // The positions of the hostiles the player can see within `range`
+  fn hostiles_in_view( world : &World, range : u32 ) -> Vec<Position> {
+    let player = world.player();
+    
+    world.actors_within( DOOR, range ).into_iter()
+      .filter( |&e| world.are_hostile( player, e ) )
+      .filter_map( |e| world.entities.position( e ) )
+      .filter( |&p| world.can_see( p ) )
+      .collect()
+  }
+  
+  #[test]
+  fn starts_on_the_closest_hostile() {
+    let world = world_at_the_door();
+    let hostiles = hostiles_in_view( &world, 8 );
+    let targeting = Targeting::new( &world, DOOR, 8 );
+    
+    assert!( hostiles.contains( &targeting.cursor ) );
+    assert!( hostiles.iter().all( |&p| DOOR.distance( p ) >= DOOR.distance( targeting.cursor ) ) );
+  }
+  
+  #[test]
+  fn cycle_visits_every_hostile_in_view_and_wraps_around() {
+    let world = world_at_the_door();
+    let hostiles = hostiles_in_view( &world, 8 );
+    assert!( hostiles.len() >= 2, "the test map should show several hostiles" );
+    
+    let mut targeting = Targeting::new( &world, DOOR, 8 );
+    let first = targeting.cursor;
+    let mut visited = vec![ first ];
+    
+    for _ in 1..hostiles.len() {
+      targeting.cycle( &world );
+      
+      assert!( !visited.contains( &targeting.cursor ) );
+      visited.push( targeting.cursor );
+    }
+    
+    assert!( hostiles.iter().all( |p| visited.contains( p ) ) );
+    
+    targeting.cycle( &world );
+    assert_eq!( targeting.cursor, first );
+  }
+  
+  #[test]
+  fn cycle_skips_hostiles_out_of_range() {
+    let world = world_at_the_door();
+    let near = hostiles_in_view( &world, 2 );
+    assert!( !near.is_empty() && near.len() < hostiles_in_view( &world, 8 ).len() );
+    
+    let mut targeting = Targeting::new( &world, DOOR, 2 );
+    
+    for _ in 0..near.len() * 2 {
+      assert!( near.contains( &targeting.cursor ) );
+      targeting.cycle( &world );
+    }
+  }
+  
+  #[test]
+  fn cursor_stays_put_without_hostiles_in_view() {
+    let mut world = test_world();
+    world.update_lighting();
+    
+    let player = world.player();
+    let origin = world.entities.position( player ).expect( "player has no position" );
+    let mut targeting = Targeting::new( &world, origin, 8 );
+    assert_eq!( targeting.cursor, origin );
+    
+    targeting.cycle( &world );
+    assert_eq!( targeting.cursor, origin );
+  }
+}
@@ -0,0 +1,40 @@
+use map::NORMAL_MOVEMENT_COST;
+
+/// What happens to an actor stepping onto a trap.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TrapKind {
+  /// Drops the actor into a pit, hurting it
+  Pit,
+  /// Shoots a poisoned dart at the actor
+  Dart,
+  /// Sends the actor somewhere else on the level
+  Teleport,
+  /// Sounds an alarm heard all around
+  Alarm
+}
+
+impl TrapKind {
+  pub fn from_config( name : &str ) -> Option<TrapKind> {
+    Some( match name {
+      "pit"      => TrapKind::Pit,
+      "dart"     => TrapKind::Dart,
+      "teleport" => TrapKind::Teleport,
+      "alarm"    => TrapKind::Alarm,
+      _          => return None
+    } )
+  }
+}
+
+/// The damage taken from falling into a pit.
+pub const PIT_DAMAGE : u32 = 4;
+
+/// The damage a dart deals on hitting.
+pub const DART_DAMAGE : u32 = 1;
+
+/// How long the poison of a dart lasts, measured in the same units as
+/// `Action::duration`.
+pub const DART_POISON_DURATION : u32 = 500;
+
+/// What stepping onto a trap known to be there counts as when planning a
+/// path, so paths lead around it unless there's no other way.
+pub const KNOWN_TRAP_PATH_COST : u32 = 20 * NORMAL_MOVEMENT_COST;
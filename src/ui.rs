@@ -1,5 +1,6 @@
 use tcod::console::{TextAlignment, BackgroundFlag};
 use tcod::{Console, RootConsole};
+use tcod::input::{Key, KeyCode};
 
 use ::util::ScreenPos;
 
@@ -19,7 +20,7 @@ impl TextField {
       alignment: alignment
     }
   }
-
+  
   pub fn render<C : Console>( &self, ctx : &mut C ) {
     ctx.print_ex( self.position.x, self.position.y
                 , BackgroundFlag::None
@@ -101,15 +102,19 @@ impl SelectionList {
   }
   
   pub fn update( &mut self, root : &mut RootConsole ) -> Option<usize> {
-    use ::tcod::input::{KeyCode};
-    
     let mkey = root.check_for_keypress( ::tcod::input::KEY_PRESSED );
     
     if mkey.is_none() {
       return None;
     }
     
-    match mkey.unwrap().code {
+    self.handle_key( mkey.unwrap() )
+  }
+  
+  /// Moves the selection according to the key, returning the selection if
+  /// the key confirmed it.
+  pub fn handle_key( &mut self, key : Key ) -> Option<usize> {
+    match key.code {
       KeyCode::Up => self.prev_selection(),
       KeyCode::Down => self.next_selection(),
       KeyCode::Enter => return Some( self.get_selection() ),
@@ -119,3 +124,191 @@ impl SelectionList {
     None
   }
 }
+
+/// A framed list of entries, each picked by pressing the letter shown next
+/// to it. Lists with more entries than there are letters are split into
+/// pages, turned with `<` and `>`.
+pub struct LetterMenu {
+  position : ScreenPos,
+  title    : String,
+  entries  : Vec<String>,
+  page     : usize
+}
+
+// The number of entries shown at once, one for each letter
+const MENU_PAGE_SIZE : usize = 26;
+
+impl LetterMenu {
+  pub fn new( title : String, entries : Vec<String>, position : ScreenPos ) -> LetterMenu {
+    LetterMenu {
+      position: position,
+      title:    title,
+      entries:  entries,
+      page:     0
+    }
+  }
+  
+  /// Shows the given page instead of the first one. Pages past the end show
+  /// the last page.
+  pub fn on_page( mut self, page : usize ) -> LetterMenu {
+    self.page = ::std::cmp::min( page, self.page_count() - 1 );
+    self
+  }
+  
+  pub fn page_count( &self ) -> usize {
+    ::std::cmp::max( (self.entries.len() + MENU_PAGE_SIZE - 1) / MENU_PAGE_SIZE, 1 )
+  }
+  
+  // The indices of the entries on the current page
+  fn shown( &self ) -> ::std::ops::Range<usize> {
+    let start = self.page * MENU_PAGE_SIZE;
+    
+    start..::std::cmp::min( start + MENU_PAGE_SIZE, self.entries.len() )
+  }
+  
+  // The letter the entry at `idx` on the page is picked with
+  fn letter( idx : usize ) -> char {
+    (b'a' + idx as u8) as char
+  }
+  
+  pub fn render<C : Console>( &self, ctx : &mut C ) {
+    let title = if self.page_count() > 1 {
+      format!( "{} ({}/{}, < > for more)", self.title, self.page + 1, self.page_count() )
+    } else {
+      self.title.clone()
+    };
+    
+    let shown = &self.entries[ self.shown() ];
+    let widest = shown.iter()
+      .fold( title.len(), |x, e| if e.len() + 3 > x { e.len() + 3 } else { x } );
+    
+    ctx.print_frame( self.position.x, self.position.y
+                   , widest as i32 + 2, shown.len() as i32 + 2
+                   , true, BackgroundFlag::Set, Some( &title ) );
+    
+    for (i, entry) in shown.iter().enumerate() {
+      ctx.print_ex( self.position.x + 1, self.position.y + 1 + i as i32
+                  , BackgroundFlag::None, TextAlignment::Left
+                  , format!( "{}) {}", LetterMenu::letter( i ), entry ) );
+    }
+  }
+  
+  /// The page the given key turns the menu to, if it's one that turns pages.
+  pub fn turn_page( &self, key : Key ) -> Option<usize> {
+    if key.code != KeyCode::Char {
+      return None
+    }
+    
+    match key.printable {
+      '>' if self.page + 1 < self.page_count() => Some( self.page + 1 ),
+      '<' if self.page > 0 => Some( self.page - 1 ),
+      _ => None
+    }
+  }
+  
+  /// The index of the entry picked with the given key, if any, counted from
+  /// the first entry of the whole list.
+  pub fn selection( &self, key : Key ) -> Option<usize> {
+    if key.code != KeyCode::Char {
+      return None
+    }
+    
+    self.shown().find( |&i| LetterMenu::letter( i % MENU_PAGE_SIZE ) == key.printable )
+  }
+}
+
+/// A line of text the player can type into.
+pub struct TextInput {
+  position : ScreenPos,
+  prompt   : String,
+  text     : String,
+  max_len  : usize
+}
+
+impl TextInput {
+  pub fn new( prompt : String, position : ScreenPos, max_len : usize ) -> TextInput {
+    TextInput {
+      position: position,
+      prompt:   prompt,
+      text:     String::new(),
+      max_len:  max_len
+    }
+  }
+  
+  pub fn render<C : Console>( &self, ctx : &mut C ) {
+    ctx.print_ex( self.position.x, self.position.y
+                , BackgroundFlag::None
+                , TextAlignment::Left
+                , format!( "{}{}_", self.prompt, self.text ) );
+  }
+  
+  /// Handles the keys typed by the player, returning the text once it's
+  /// confirmed with enter. Empty text can't be confirmed.
+  pub fn update( &mut self, root : &mut RootConsole ) -> Option<String> {
+    let key = match root.check_for_keypress( ::tcod::input::KEY_PRESSED ) {
+      Some( key ) => key,
+      None => return None
+    };
+    
+    match key.code {
+      KeyCode::Enter if !self.text.trim().is_empty() =>
+        return Some( self.text.trim().to_string() ),
+      KeyCode::Backspace => { self.text.pop(); },
+      KeyCode::Char | KeyCode::Spacebar | KeyCode::Number0 | KeyCode::Number1
+      | KeyCode::Number2 | KeyCode::Number3 | KeyCode::Number4 | KeyCode::Number5
+      | KeyCode::Number6 | KeyCode::Number7 | KeyCode::Number8 | KeyCode::Number9 =>
+        if self.text.chars().count() < self.max_len && !key.printable.is_control() {
+          self.text.push( key.printable );
+        },
+      _ => {}
+    }
+    
+    None
+  }
+}
+
+// The number of lines the text of a dialogue panel can take up
+const DIALOGUE_TEXT_HEIGHT : i32 = 6;
+
+/// A framed panel showing what someone says, with the answers to pick from
+/// listed below.
+pub struct DialoguePanel {
+  position : ScreenPos,
+  width    : i32,
+  speaker  : String,
+  text     : String,
+  choices  : SelectionList
+}
+
+impl DialoguePanel {
+  pub fn new( speaker : String, text : String, choices : Vec<String>
+            , position : ScreenPos, width : i32 ) -> DialoguePanel {
+    
+    let list_pos = (position.x + width / 2, position.y + DIALOGUE_TEXT_HEIGHT + 2).into();
+    
+    DialoguePanel {
+      position: position,
+      width:    width,
+      speaker:  speaker,
+      text:     text,
+      choices:  SelectionList::new( choices, list_pos, true, TextAlignment::Center )
+    }
+  }
+  
+  pub fn render<C : Console>( &self, ctx : &mut C ) {
+    let height = DIALOGUE_TEXT_HEIGHT + self.choices.elements.len() as i32 + 3;
+    
+    ctx.print_frame( self.position.x, self.position.y, self.width, height
+                   , true, BackgroundFlag::Set, Some( &self.speaker ) );
+    ctx.print_rect( self.position.x + 2, self.position.y + 1
+                  , self.width - 4, DIALOGUE_TEXT_HEIGHT, &self.text );
+    
+    self.choices.render( ctx );
+  }
+  
+  /// Moves the selection according to the key, returning the index of the
+  /// answer if the key picked it.
+  pub fn handle_key( &mut self, key : Key ) -> Option<usize> {
+    self.choices.handle_key( key )
+  }
+}
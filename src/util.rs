@@ -1,12 +1,13 @@
 
 use map::Map;
 use toml::Value;
+use tcod::Color;
 use std::collections::BTreeMap;
 
 pub type Position = Pos<u32>;
 pub type ScreenPos = Pos<i32>;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, RustcDecodable)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, RustcDecodable)]
 pub struct Pos<N> {
   pub x : N,
   pub y : N
@@ -21,6 +22,48 @@ impl<N> Pos<N> {
   }
 }
 
+impl Pos<u32> {
+  /// The number of steps between two positions, when diagonal steps are
+  /// allowed.
+  pub fn distance( self, other : Pos<u32> ) -> u32 {
+    use std::cmp::max;
+    
+    let dx = ( self.x as i64 - other.x as i64 ).abs();
+    let dy = ( self.y as i64 - other.y as i64 ).abs();
+    
+    max( dx, dy ) as u32
+  }
+}
+
+/// A rectangular part of the map.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, RustcDecodable)]
+pub struct Area {
+  pub x      : u32,
+  pub y      : u32,
+  pub width  : u32,
+  pub height : u32
+}
+
+impl Area {
+  pub fn contains( &self, pos : Position ) -> bool {
+    pos.x >= self.x && pos.x < self.x + self.width
+    && pos.y >= self.y && pos.y < self.y + self.height
+  }
+  
+  /// Every position in the area, row by row.
+  pub fn positions( &self ) -> Vec<Position> {
+    let mut positions = Vec::new();
+    
+    for y in self.y..self.y + self.height {
+      for x in self.x..self.x + self.width {
+        positions.push( Position::new( x, y ) );
+      }
+    }
+    
+    positions
+  }
+}
+
 impl<M : Into<N>, N> Into<(N, N)> for Pos<M> {
   fn into( self ) -> (N, N) {
     (self.x.into(), self.y.into())
@@ -33,6 +76,19 @@ impl<N> From<(N, N)> for Pos<N> {
   }
 }
 
+/// A colour as it's written in the data files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, RustcDecodable)]
+pub struct Rgb {
+  pub r : u8,
+  pub g : u8,
+  pub b : u8
+}
+
+impl Into<Color> for Rgb {
+  fn into( self ) -> Color {
+    Color::new( self.r, self.g, self.b )
+  }
+}
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Direction {
@@ -46,7 +102,39 @@ pub enum Direction {
   SouthWest
 }
 
+pub const DIRECTIONS : [Direction; 8] =
+  [ Direction::North, Direction::East, Direction::South, Direction::West
+  , Direction::NorthEast, Direction::NorthWest
+  , Direction::SouthEast, Direction::SouthWest ];
+
 impl Direction {
+  pub fn random() -> Direction {
+    use ::tcod::random::Rng;
+    
+    let idx = Rng::get_instance().get_int( 0, DIRECTIONS.len() as i32 - 1 );
+    
+    DIRECTIONS[ idx as usize ]
+  }
+  
+  /// The direction of the step to take to go from `from` towards `to`, or
+  /// `None` if they're the same position.
+  pub fn towards( from : Position, to : Position ) -> Option<Direction> {
+    use std::cmp::Ordering::*;
+    use self::Direction::*;
+    
+    Some( match (to.x.cmp( &from.x ), to.y.cmp( &from.y )) {
+      (Equal, Less) => North,
+      (Greater, Equal) => East,
+      (Equal, Greater) => South,
+      (Less, Equal) => West,
+      (Greater, Less) => NorthEast,
+      (Less, Less) => NorthWest,
+      (Greater, Greater) => SouthEast,
+      (Less, Greater) => SouthWest,
+      (Equal, Equal) => return None
+    } )
+  }
+  
   pub fn try_offset_position( self, pos : Position, map : &Map )
     -> Option<Position> {
     use self::Direction::*;
@@ -104,3 +192,20 @@ pub fn load_data_file( filename : &str ) -> BTreeMap<String, Value> {
   }
 }
 
+
+/// Randomly succeeds with the given chance in percent.
+pub fn roll_percent( chance : u32 ) -> bool {
+  use ::tcod::random::Rng;
+  
+  Rng::get_instance().get_int( 1, 100 ) <= chance as i32
+}
+
+/// Returns the string with its first letter in upper case.
+pub fn capitalize( s : &str ) -> String {
+  let mut chars = s.chars();
+  
+  match chars.next() {
+    Some( first ) => first.to_uppercase().chain( chars ).collect(),
+    None => String::new()
+  }
+}
@@ -1,71 +1,1465 @@
 use std::path::{Path};
-use std::cell::{RefCell};
+use std::cell::{Ref, RefCell};
+use std::collections::HashSet;
+use std::mem;
 
-use ::tcod::{Console};
+use ::tcod::{Console, Color};
 
 use ::map::*;
+use ::description::*;
 use ::util::*;
 use ::actor::*;
-use ::player::*;
+use ::entity::*;
+use ::event::*;
+use ::status::*;
+use ::light::*;
+use ::spatial::*;
+use ::monster::*;
+use ::item::*;
+use ::ai;
+use ::ai::{Ai, Behaviour};
+use ::faction::{Faction, PLAYER_FACTION};
+use ::shop::*;
+use ::dialogue::*;
+use ::quest::*;
+use ::equipment::*;
+use ::hunger::*;
+use ::character::Character;
+use ::experience::*;
+use ::skill::*;
+use ::spell::*;
+use ::noise;
+use ::noise::*;
+use ::pathfind;
+use ::trap::*;
 
 enum SpawnCommands {
-  SpawnItem,
-  SpawnActor( Position )
+  Despawn( Entity )
 }
 
 pub struct World {
   pub map : Map,
-  pub player : RefCell<Player>,
-  actors : Vec<RefCell<Actor>>,
-  items  : Vec<RefCell<Item>>,
-  spawns : Vec<SpawnCommands>
+  pub entities : Entities,
+  pub lighting : LightMap,
+  pub occupancy : Occupancy,
+  events : RefCell<Vec<Event>>,
+  spawns : Vec<SpawnCommands>,
+  // The kinds of items the player has learned to recognize
+  identified : HashSet<String>,
+  /// Who the player is playing as
+  pub character : Character,
+  /// The quests the player has taken on
+  pub journal : Journal
 }
 
 impl World {
-  pub fn new( map_path : &str ) -> Result<World, MapLoadingError> {
+  pub fn new( map_path : &str, character : Character ) -> Result<World, MapLoadingError> {
     let map = try!( Map::load( map_path ) );
-    let mut player = Player::new( map.player_position );
-    player.actor.graphics.symbol = '@';
     
-    Ok( World {
+    let lighting = LightMap::new( map.width, map.height );
+    let occupancy = Occupancy::new( map.width, map.height );
+    
+    let mut world = World {
       map:    map,
-      player: RefCell::new( player ),
-      actors: Vec::new(),
-      items:  Vec::new(),
-      spawns: Vec::new()
-    } )
+      entities: Entities::new(),
+      lighting: lighting,
+      occupancy: occupancy,
+      events: RefCell::new( Vec::new() ),
+      spawns: Vec::new(),
+      identified: HashSet::new(),
+      character: character,
+      journal: Journal::new()
+    };
+    
+    let player_position = world.map.player_position;
+    let player = world.spawn_player( player_position );
+    
+    if let Some( kind ) = world.character.pet() {
+      world.spawn_follower( &kind, player );
+    }
+    
+    for (kind, pos) in world.map.monsters.clone() {
+      world.spawn_monster( &kind, pos );
+    }
+    
+    for (kind, pos) in world.map.items.clone() {
+      world.spawn_item( &kind, pos );
+    }
+    
+    for shop in world.map.shops.clone() {
+      world.spawn_shop( &shop );
+    }
+    
+    let depth = world.map.depth;
+    world.emit( Event::LevelEntered { depth: depth } );
+    
+    Ok( world )
+  }
+  
+  fn spawn_player( &mut self, pos : Position ) -> Entity {
+    let e = self.entities.create();
+    
+    self.entities.kinds.insert( e, Kind::new( "actor.player" ) );
+    self.entities.positions.insert( e, pos );
+    self.entities.graphics.insert( e, Graphics { symbol: '@', fg: self.character.color() } );
+    self.entities.stats.insert( e, self.character.stats() );
+    self.entities.actors.insert( e, Actor::new( MovementType::Walking ) );
+    self.entities.lights.insert( e, LightSource::new( 5, Color::new( 255, 210, 140 ) ) );
+    self.entities.inventories.insert( e, Inventory::new() );
+    self.entities.equipment.insert( e, Equipment::new() );
+    self.entities.hunger.insert( e, Hunger::new( STARTING_FOOD ) );
+    self.entities.experience.insert( e, Experience::new() );
+    self.entities.skills.insert( e, Skills::from_config( &self.character.skills() ) );
+    self.entities.mana.insert( e, Mana::new( self.character.mana() ) );
+    self.entities.spellbooks.insert( e, Spellbook::from_config( &self.character.spells() ) );
+    self.entities.player_controlled.insert( e, PlayerControlled );
+    self.entities.purses.insert( e, Purse { gold: self.character.gold() } );
+    self.entities.factions.insert( e, Faction::from_config( PLAYER_FACTION )
+                                        .expect( "the player's faction is missing" ) );
+    self.occupancy.insert( pos, e );
+    
+    for kind in self.character.items() {
+      let item = self.spawn_carried_item( &kind );
+      self.entities.inventories.get_mut( e ).expect( "player has no inventory" ).items.push( item );
+    }
+    
+    for kind in self.character.equipment() {
+      let item = self.spawn_carried_item( &kind );
+      let slot = self.entities.equippables.get( item )
+        .map( |equip| equip.slots[ 0 ] )
+        .expect( &format!( "The starting equipment `{}` can't be equipped", kind ) );
+      
+      self.entities.equipment.get_mut( e ).expect( "player has no equipment" ).equip( slot, item );
+    }
+    
+    e
+  }
+  
+  // Creates an item of the given kind that isn't lying anywhere, for the
+  // player to start out with. The player knows what their own things are.
+  fn spawn_carried_item( &mut self, kind : &str ) -> Entity {
+    self.identify( kind );
+    
+    let item = self.spawn_item( kind, Position::new( 0, 0 ) );
+    self.entities.positions.remove( item );
+    
+    item
+  }
+  
+  /// Places a new monster of the given kind in the world, unless the
+  /// position is already taken or the monster couldn't move there.
+  fn spawn_monster( &mut self, kind : &str, pos : Position ) -> Option<Entity> {
+    let template = monster_template( kind )
+      .expect( &format!( "No monster of kind `{}` exists", kind ) );
+    
+    let passable = self.map.tile_at( pos ).movement_cost( template.movement ).is_some();
+    
+    if self.occupancy.is_occupied( pos ) || !passable {
+      return None
+    }
+    
+    let e = self.entities.create();
+    
+    self.entities.kinds.insert( e, Kind::new( &format!( "monster.{}", kind ) ) );
+    self.entities.positions.insert( e, pos );
+    self.entities.graphics.insert( e, Graphics { symbol: template.symbol
+                                               , fg: template.color } );
+    self.entities.stats.insert( e, Stats { health: template.health
+                                         , max_health: template.health
+                                         , speed: template.speed
+                                         , attack: template.attack
+                                         , defense: template.defense } );
+    self.entities.actors.insert( e, Actor::new( template.movement ) );
+    let mut ai = Ai::new( template.behaviour, template.sight );
+    ai.asleep = template.asleep;
+    
+    self.entities.ais.insert( e, ai );
+    self.entities.factions.insert( e, template.faction );
+    self.entities.rewards.insert( e, ExperienceReward { points: template.experience } );
+    self.entities.skills.insert( e, Skills::from_config( &template.skills ) );
+    
+    if let Some( dialogue ) = template.dialogue {
+      self.entities.speakers.insert( e, Speaker { dialogue: dialogue } );
+    }
+    
+    if !template.spells.is_empty() {
+      self.entities.mana.insert( e, Mana::new( template.mana ) );
+      self.entities.spellbooks.insert( e, Spellbook::from_config( &template.spells ) );
+    }
+    
+    if let Some( light ) = template.light {
+      self.entities.lights.insert( e, light );
+    }
+    
+    self.occupancy.insert( pos, e );
+    
+    Some( e )
+  }
+  
+  /// Places a new monster of the given kind next to `leader`, joining its
+  /// side and following it around.
+  fn spawn_follower( &mut self, kind : &str, leader : Entity ) -> Option<Entity> {
+    let pos = self.entities.position( leader ).expect( "leader has no position" );
+    
+    let spots = ::pathfind::neighbours( &self.map, pos );
+    
+    let e = match spots.into_iter().filter_map( |p| self.spawn_monster( kind, p ) ).next() {
+      Some( e ) => e,
+      None => return None
+    };
+    
+    if let Some( mut ai ) = self.entities.ais.get_mut( e ) {
+      ai.behaviour = Behaviour::Follow;
+      ai.leader = Some( leader );
+    }
+    
+    self.join_faction( e, leader );
+    
+    Some( e )
+  }
+  
+  // Places the shopkeeper and lays out its stock on the free tiles of the
+  // shop, for as long as there's room
+  fn spawn_shop( &mut self, shop : &ShopPlacement ) {
+    let keeper = self.spawn_monster( &shop.keeper, shop.position )
+      .expect( &format!( "The shopkeeper `{}` can't be placed", shop.keeper ) );
+    
+    self.entities.shopkeepers.insert( keeper, Shopkeeper { area: shop.area } );
+    
+    let spots : Vec<Position> = shop.area.positions().into_iter()
+      .filter( |&p| {
+        !self.map.tile_at( p ).is_solid()
+        && !self.occupancy.is_occupied( p )
+        && self.items_at( p ).is_empty()
+      } )
+      .collect();
+    
+    for (kind, &pos) in shop.stock.iter().zip( spots.iter() ) {
+      let item = self.spawn_item( kind, pos );
+      self.entities.for_sale.insert( item, ForSale { keeper: keeper } );
+    }
+  }
+  
+  // Puts the entity on the same side as `other`
+  fn join_faction( &mut self, e : Entity, other : Entity ) {
+    match self.entities.factions.get( other ).map( |f| *f ) {
+      Some( faction ) => self.entities.factions.insert( e, faction ),
+      None => { self.entities.factions.remove( e ); }
+    }
+  }
+  
+  /// Places a new item of the given kind on the ground.
+  fn spawn_item( &mut self, kind : &str, pos : Position ) -> Entity {
+    let template = item_template( kind )
+      .expect( &format!( "No item of kind `{}` exists", kind ) );
+    
+    let e = self.entities.create();
+    
+    self.entities.kinds.insert( e, Kind::new( &format!( "item.{}", kind ) ) );
+    self.entities.positions.insert( e, pos );
+    self.entities.graphics.insert( e, Graphics { symbol: template.symbol
+                                               , fg: template.color } );
+    self.entities.items.insert( e, Item );
+    
+    if let Some( ranged ) = template.ranged {
+      self.entities.ranged_weapons.insert( e, ranged );
+    }
+    
+    if let Some( equip ) = template.equip {
+      self.entities.equippables.insert( e, equip );
+    }
+    
+    if let Some( consumable ) = template.consumable {
+      self.entities.consumables.insert( e, consumable );
+    }
+    
+    if let Some( price ) = template.price {
+      self.entities.prices.insert( e, Price( price ) );
+    }
+    
+    if let Some( gold ) = template.gold {
+      self.entities.purses.insert( e, Purse { gold: gold } );
+    }
+    
+    if let Some( name ) = template.unidentified {
+      if !self.identified.contains( kind ) {
+        self.entities.unidentified.insert( e, Unidentified { kind: kind.to_string(), name: name } );
+      }
+    }
+    
+    e
+  }
+  
+  fn despawn( &mut self, e : Entity ) {
+    if let Some( pos ) = self.entities.position( e ) {
+      self.occupancy.remove( pos, e );
+    }
+    
+    self.entities.destroy( e );
   }
   
   pub fn update( &mut self ) {
+    // Spawns are carried out at the start of the next update, so listeners
+    // can still look up the entities of the events of the last one
+    let spawns : Vec<SpawnCommands> = self.spawns.drain( 0.. ).collect();
+    
+    for cmd in spawns {
+      match cmd {
+        SpawnCommands::Despawn( e ) => self.despawn( e )
+      }
+    }
+    
+    for e in self.entities.ais.entities() {
+      ai::update( self, e );
+    }
+    
     let duration = self.shortest_action_duration();
     
     if duration == 0 {
       return
     }
     
-    self.player.borrow_mut().update( &self, duration );
+    let mut completed = Vec::new();
     
-    self.actors.retain( |actor_cell| {
-      let mut actor = actor_cell.borrow_mut();
-      actor.update( duration );
+    for (e, actor) in self.entities.actors.iter() {
+      let mut stats = self.entities.stats.get_mut( e ).expect( "actor has no stats" );
+      let health = stats.health;
+      
+      if let Some( kind ) = actor.borrow_mut().update( duration, &mut stats ) {
+        completed.push( (e, kind) );
+      }
       
-      actor.active
-    } );
+      if !self.can_heal( e ) && stats.health > health {
+        stats.health = health;
+      }
+    }
     
-    for cmd in self.spawns.drain( 0.. ) {
-      match cmd {
-        _ => {}
+    self.update_hunger( duration );
+    
+    for (_, mana) in self.entities.mana.iter() {
+      mana.borrow_mut().regenerate( duration );
+    }
+    
+    for (e, kind) in completed {
+      // An actor might have been killed before its action was completed
+      if self.entities.actors.has( e ) {
+        self.complete_action( e, kind );
       }
     }
   }
   
-  fn shortest_action_duration( &mut self ) -> u32 {
-    let mut shortest = self.player.borrow().actor.action.duration;
+  // Digests food for everything that gets hungry, starving those that have
+  // run out
+  fn update_hunger( &mut self, duration : u32 ) {
+    let mut starving = Vec::new();
+    
+    for (e, hunger) in self.entities.hunger.iter() {
+      let mut hunger = hunger.borrow_mut();
+      let stage = hunger.stage();
+      let starved = hunger.advance( duration );
+      
+      if hunger.stage() != stage {
+        self.emit( Event::HungerChanged { entity: e, stage: hunger.stage() } );
+      }
+      
+      if starved > 0 {
+        starving.push( (e, starved) );
+      }
+    }
+    
+    for (e, damage) in starving {
+      let alive = match self.entities.stats.get_mut( e ) {
+        Some( mut stats ) => {
+          stats.health = stats.health.saturating_sub( damage );
+          stats.health > 0
+        },
+        None => continue
+      };
+      
+      if !alive {
+        self.kill( e, None );
+      }
+    }
+  }
+  
+  /// Queues an event for the listeners.
+  pub fn emit( &self, event : Event ) {
+    self.events.borrow_mut().push( event );
+  }
+  
+  /// Takes all the events that have happened since the last time.
+  pub fn take_events( &self ) -> Vec<Event> {
+    mem::replace( &mut *self.events.borrow_mut(), Vec::new() )
+  }
+  
+  /// Queues an entity to be removed from the world at the start of the
+  /// next update.
+  pub fn queue_despawn( &mut self, e : Entity ) {
+    self.spawns.push( SpawnCommands::Despawn( e ) );
+  }
+  
+  // Carries out the effects a completed action has on the world
+  fn complete_action( &mut self, e : Entity, kind : ActionKind ) {
+    match kind {
+      ActionKind::MoveTo( pos ) => {
+        // Someone else might have gotten there first
+        if let Some( other ) = self.actor_at( pos ) {
+          let reason = ActionFailureReason::BlockedByActor( other );
+          self.emit( Event::ActionFailed { entity: e, reason: reason } );
+          
+          return
+        }
+        
+        let from = self.entities.position( e ).expect( "actor has no position" );
+        self.occupancy.move_actor( from, pos, e );
+        self.entities.positions.insert( e, pos );
+        
+        self.emit( Event::Moved { entity: e, from: from, to: pos } );
+        self.make_noise( Some( e ), pos, STEP_NOISE );
+        self.check_for_theft( e, from, pos );
+        
+        if let Tile::Trap( kind ) = self.map.actual_tile_at( pos ) {
+          self.spring_trap( e, pos, kind );
+        }
+      },
+      ActionKind::SwapPlaces( other ) => {
+        let pos = self.entities.position( e ).expect( "actor has no position" );
+        
+        // The other actor might have moved away in the meantime
+        let other_pos = match self.entities.position( other ) {
+          Some( other_pos ) if pos.distance( other_pos ) == 1 => other_pos,
+          _ => return
+        };
+        
+        self.occupancy.remove( pos, e );
+        self.occupancy.move_actor( other_pos, pos, other );
+        self.occupancy.insert( other_pos, e );
+        self.entities.positions.insert( e, other_pos );
+        self.entities.positions.insert( other, pos );
+        
+        self.emit( Event::SwappedPlaces { entity: e, other: other } );
+        self.emit( Event::Moved { entity: e, from: pos, to: other_pos } );
+        self.emit( Event::Moved { entity: other, from: other_pos, to: pos } );
+        self.make_noise( Some( e ), other_pos, STEP_NOISE );
+        self.check_for_theft( e, pos, other_pos );
+        self.check_for_theft( other, other_pos, pos );
+      },
+      ActionKind::Attack( target ) => {
+        let pos = self.entities.position( e ).expect( "actor has no position" );
+        
+        // The target might have died or moved away in the meantime
+        match self.entities.position( target ) {
+          Some( target_pos ) if pos.distance( target_pos ) <= 1 => {},
+          _ => return
+        }
+        
+        self.attack( e, target );
+        self.make_noise( Some( e ), pos, FIGHT_NOISE );
+      },
+      ActionKind::PickUp( item ) => {
+        let pos = self.entities.position( e );
+        
+        // Someone else might have picked it up first
+        if pos.is_none() || self.entities.position( item ) != pos {
+          return
+        }
+        
+        self.entities.positions.remove( item );
+        
+        // Gold goes straight into the purse
+        let gold = self.entities.purses.get( item ).map( |p| p.gold );
+        
+        match gold {
+          Some( gold ) => {
+            if let Some( mut purse ) = self.entities.purses.get_mut( e ) {
+              purse.gold += gold;
+            }
+            
+            self.queue_despawn( item );
+          },
+          None => if let Some( mut inventory ) = self.entities.inventories.get_mut( e ) {
+            inventory.items.push( item );
+          }
+        }
+        
+        self.emit( Event::PickedUp { entity: e, item: item } );
+      },
+      ActionKind::Fire( weapon, target ) => {
+        // The weapon might have been lost in the meantime
+        let carried = self.entities.inventories.get( e )
+          .map( |i| i.items.contains( &weapon ) )
+          .unwrap_or( false );
+        let wielded = self.entities.equipment.get( e )
+          .map( |eq| eq.get( EquipSlot::Weapon ) == Some( weapon ) )
+          .unwrap_or( false );
+        
+        if carried || wielded {
+          self.fire( e, weapon, target );
+        }
+      },
+      ActionKind::Equip( item, slot ) => {
+        // The item might have been lost in the meantime
+        let carried = self.entities.inventories.get( e )
+          .map( |i| i.items.contains( &item ) )
+          .unwrap_or( false );
+        
+        if carried {
+          self.equip( e, item, slot );
+        }
+      },
+      ActionKind::Unequip( slot ) => self.unequip( e, slot ),
+      ActionKind::Use( item ) => {
+        // The item might have been lost in the meantime
+        let carried = self.entities.inventories.get( e )
+          .map( |i| i.items.contains( &item ) )
+          .unwrap_or( false );
+        
+        if carried {
+          self.use_item( e, item );
+        }
+      },
+      ActionKind::Cast( id, target ) => self.cast( e, id, target ),
+      ActionKind::Buy( item ) => self.buy( e, item ),
+      ActionKind::Sell( item, keeper ) => {
+        // The item might have been lost in the meantime
+        let carried = self.entities.inventories.get( e )
+          .map( |i| i.items.contains( &item ) )
+          .unwrap_or( false );
+        
+        if carried && self.is_alive( keeper ) {
+          self.sell( e, item, keeper );
+        }
+      },
+      ActionKind::Search => {
+        let found = self.search( e );
+        self.emit( Event::Searched { entity: e, found: found } );
+      },
+      ActionKind::Interact( pos, how ) => {
+        // Someone might have stepped into the doorway in the meantime
+        if how == Interaction::Close {
+          if let Some( other ) = self.actor_at( pos ) {
+            let reason = ActionFailureReason::BlockedByActor( other );
+            self.emit( Event::ActionFailed { entity: e, reason: reason } );
+            
+            return
+          }
+        }
+        
+        if let Some( tile ) = self.map.tile_at( pos ).interact( how ) {
+          self.map.set_tile( pos, tile );
+          
+          let from = self.entities.position( e ).expect( "actor has no position" );
+          self.make_noise( Some( e ), from, DOOR_NOISE );
+        }
+      },
+      _ => {}
+    }
+  }
+  
+  /// Looks for hidden things next to the entity, returning how many were
+  /// found. Each one is spotted with the chance of the searching skill.
+  fn search( &mut self, e : Entity ) -> u32 {
+    let pos = self.entities.position( e ).expect( "actor has no position" );
+    
+    let chance = match self.entities.skills.get( e ) {
+      Some( skills ) => skills.chance( SkillKind::Searching ),
+      None => Skills::new().chance( SkillKind::Searching )
+    };
+    
+    self.practice( e, SkillKind::Searching );
     
-    self.actors.iter().fold( shortest, | sh, ac | {
+    let mut found = 0;
+    
+    for p in pathfind::neighbours( &self.map, pos ) {
+      if self.map.is_hidden( p ) && roll_percent( chance ) {
+        self.map.reveal( p );
+        found += 1;
+      }
+    }
+    
+    found
+  }
+  
+  // Sets off the trap the entity stepped on at `pos`, which is hidden no
+  // longer
+  fn spring_trap( &mut self, e : Entity, pos : Position, kind : TrapKind ) {
+    self.map.reveal( pos );
+    self.emit( Event::TrapSprung { entity: e, trap: kind } );
+    
+    match kind {
+      TrapKind::Pit => self.hurt( e, PIT_DAMAGE ),
+      TrapKind::Dart => {
+        self.hurt( e, DART_DAMAGE );
+        
+        if let Some( mut actor ) = self.entities.actors.get_mut( e ) {
+          actor.effects.apply( StatusKind::Poison, DART_POISON_DURATION );
+        }
+      },
+      TrapKind::Teleport => self.teleport( e ),
+      TrapKind::Alarm => self.make_noise( None, pos, ALARM_NOISE )
+    }
+  }
+  
+  /// Rolls for the success of something the entity does with the skill,
+  /// counting it as practice.
+  pub fn skill_check( &mut self, e : Entity, kind : SkillKind ) -> bool {
+    let chance = match self.entities.skills.get( e ) {
+      Some( skills ) => skills.chance( kind ),
+      None => Skills::new().chance( kind )
+    };
+    
+    self.practice( e, kind );
+    
+    roll_percent( chance )
+  }
+  
+  // Counts a use of the skill by the entity, reporting it if it improves
+  fn practice( &mut self, e : Entity, kind : SkillKind ) {
+    let improved = self.entities.skills.get_mut( e ).and_then( |mut s| s.practice( kind ) );
+    
+    if let Some( level ) = improved {
+      self.emit( Event::SkillImproved { entity: e, skill: kind, level: level } );
+    }
+  }
+  
+  /// Makes a noise of the given intensity at `pos` for the AI controlled
+  /// actors around to react to. Noise made by an actor is muffled by its
+  /// stealth, which improves when sneaking around hostiles.
+  pub fn make_noise( &mut self, source : Option<Entity>, pos : Position, intensity : u32 ) {
+    let loudness = noise::spread( &self.map, pos, intensity );
+    
+    let listeners : Vec<(Entity, u32)> = self.entities.ais.entities().into_iter()
+      .filter( |&e| Some( e ) != source )
+      .filter_map( |e| {
+        self.entities.position( e )
+          .and_then( |p| loudness.get( &p ) )
+          .map( |&l| (e, l) )
+      } )
+      .collect();
+    
+    let stealth = match source {
+      Some( source ) => {
+        let sneaking = listeners.iter().any( |&(e, _)| self.are_hostile( source, e ) );
+        
+        if sneaking {
+          self.practice( source, SkillKind::Stealth );
+        }
+        
+        self.entities.skills.get( source ).map( |s| s.level( SkillKind::Stealth ) ).unwrap_or( 0 )
+      },
+      None => 0
+    };
+    
+    for (e, loudness) in listeners {
+      if loudness <= stealth {
+        continue;
+      }
+      
+      let woke = self.entities.ais.get_mut( e )
+        .map( |mut ai| ai.hear( pos, loudness - stealth ) )
+        .unwrap_or( false );
+      
+      if woke {
+        self.emit( Event::WokeUp { entity: e } );
+      }
+    }
+  }
+  
+  /// The path a projectile sent from `from` towards `to` takes: it flies
+  /// up to `range` tiles, stopping before walls and at the first actor in its
+  /// way.
+  pub fn projectile_path( &self, from : Position, to : Position, range : u32 )
+    -> Vec<Position> {
+    
+    let mut path = Vec::new();
+    
+    for pos in self.map.line( from, to ) {
+      if from.distance( pos ) > range {
+        break;
+      }
+      
+      path.push( pos );
+      
+      if self.occupancy.is_occupied( pos ) {
+        break;
+      }
+    }
+    
+    path
+  }
+  
+  // Sends a projectile from the weapon towards `target`, hitting the first
+  // actor in its way. A thrown weapon lands where the projectile stops.
+  fn fire( &mut self, e : Entity, weapon : Entity, target : Position ) {
+    let ranged = *self.entities.ranged_weapons.get( weapon ).expect( "weapon is not ranged" );
+    let from = self.entities.position( e ).expect( "actor has no position" );
+    let path = self.projectile_path( from, target, ranged.range );
+    
+    let mut hit = path.last().and_then( |&pos| self.occupancy.at( pos ) );
+    
+    if hit.is_some() && !self.skill_check( e, SkillKind::Ranged ) {
+      hit = None;
+    }
+    
+    if ranged.thrown {
+      if let Some( mut inventory ) = self.entities.inventories.get_mut( e ) {
+        inventory.items.retain( |&item| item != weapon );
+      }
+      
+      self.entities.positions.insert( weapon, *path.last().unwrap_or( &from ) );
+    }
+    
+    self.emit( Event::Fired { entity: e, weapon: weapon, hit: hit } );
+    
+    if let Some( target ) = hit {
+      self.hit( e, target, ranged.damage );
+    }
+  }
+  
+  // Moves the item from the inventory into the slot, putting back whatever
+  // was there before
+  fn equip( &mut self, e : Entity, item : Entity, slot : EquipSlot ) {
+    let replaced = match self.entities.equipment.get_mut( e ) {
+      Some( mut equipment ) => equipment.equip( slot, item ),
+      None => return
+    };
+    
+    if let Some( mut inventory ) = self.entities.inventories.get_mut( e ) {
+      inventory.items.retain( |&i| i != item );
+      
+      if let Some( old ) = replaced {
+        inventory.items.push( old );
+      }
+    }
+    
+    if let Some( old ) = replaced {
+      self.emit( Event::Unequipped { entity: e, item: old } );
+    }
+    
+    self.emit( Event::Equipped { entity: e, item: item } );
+  }
+  
+  fn unequip( &mut self, e : Entity, slot : EquipSlot ) {
+    let item = match self.entities.equipment.get_mut( e ).and_then( |mut eq| eq.unequip( slot ) ) {
+      Some( item ) => item,
+      None => return
+    };
+    
+    if let Some( mut inventory ) = self.entities.inventories.get_mut( e ) {
+      inventory.items.push( item );
+    }
+    
+    self.emit( Event::Unequipped { entity: e, item: item } );
+  }
+  
+  // Uses up the consumable item, applying its effect to the user
+  fn use_item( &mut self, e : Entity, item : Entity ) {
+    let effect = self.entities.consumables.get( item ).expect( "item is not consumable" ).effect;
+    
+    // Using an item is a sure way to find out what it is
+    let kind = self.entities.unidentified.get( item ).map( |u| u.kind.clone() );
+    
+    if let Some( kind ) = kind {
+      self.identify( &kind );
+    }
+    
+    match effect {
+      UseEffect::Feed( amount ) => {
+        let changed = self.entities.hunger.get_mut( e ).and_then( |mut hunger| {
+          let stage = hunger.stage();
+          hunger.eat( amount );
+          
+          if hunger.stage() != stage { Some( hunger.stage() ) } else { None }
+        } );
+        
+        if let Some( stage ) = changed {
+          self.emit( Event::HungerChanged { entity: e, stage: stage } );
+        }
+      },
+      UseEffect::Heal( amount ) => {
+        if let Some( mut stats ) = self.entities.stats.get_mut( e ) {
+          stats.health = ::std::cmp::min( stats.health + amount, stats.max_health );
+        }
+      },
+      UseEffect::Status( kind, duration ) => {
+        if let Some( mut actor ) = self.entities.actors.get_mut( e ) {
+          actor.effects.apply( kind, duration );
+        }
+      },
+      UseEffect::Teleport => self.teleport( e ),
+      UseEffect::MapLevel => {
+        for y in 0..self.map.height {
+          for x in 0..self.map.width {
+            self.map.explore( Position::new( x as u32, y as u32 ) );
+          }
+        }
+      },
+      UseEffect::Identify => {
+        let carried = self.entities.inventories.get( e )
+          .map( |i| i.items.clone() )
+          .unwrap_or( Vec::new() );
+        
+        let kinds : Vec<String> = carried.into_iter()
+          .filter_map( |i| self.entities.unidentified.get( i ).map( |u| u.kind.clone() ) )
+          .collect();
+        
+        for kind in kinds {
+          self.identify( &kind );
+        }
+      }
+    }
+    
+    if let Some( mut inventory ) = self.entities.inventories.get_mut( e ) {
+      inventory.items.retain( |&i| i != item );
+    }
+    
+    self.emit( Event::Used { entity: e, item: item, effect: effect } );
+    self.queue_despawn( item );
+  }
+  
+  // Spends the mana for the spell and, if the caster manages to work it,
+  // applies its effect
+  fn cast( &mut self, e : Entity, id : SpellId, target : Position ) {
+    let spell = spell( id );
+    
+    // The mana might have been spent on something else in the meantime
+    let paid = match self.entities.mana.get_mut( e ) {
+      Some( ref mut mana ) if mana.current >= spell.cost => {
+        mana.current -= spell.cost;
+        true
+      },
+      _ => false
+    };
+    
+    if !paid {
+      return
+    }
+    
+    if !self.skill_check( e, SkillKind::Magic ) {
+      self.emit( Event::SpellFailed { entity: e, spell: id } );
+      
+      return
+    }
+    
+    let from = self.entities.position( e ).expect( "actor has no position" );
+    
+    let (pos, affected) = match spell.targeting {
+      SpellTargeting::OnSelf => (from, Some( e )),
+      SpellTargeting::Bolt => {
+        match self.projectile_path( from, target, spell.range ).last() {
+          Some( &pos ) => (pos, self.occupancy.at( pos )),
+          None => (from, None)
+        }
+      },
+      SpellTargeting::Position => (target, self.occupancy.at( target ))
+    };
+    
+    self.emit( Event::Cast { entity: e, spell: id, target: affected } );
+    self.make_noise( Some( e ), from, SPELL_NOISE );
+    
+    match spell.effect {
+      SpellEffect::Damage( amount ) => {
+        if let Some( target ) = affected {
+          self.hit( e, target, amount );
+        }
+      },
+      SpellEffect::Heal( amount ) => {
+        if let Some( mut stats ) = affected.and_then( |t| self.entities.stats.get_mut( t ) ) {
+          stats.health = ::std::cmp::min( stats.health + amount, stats.max_health );
+        }
+      },
+      SpellEffect::Status( kind, duration ) => {
+        if let Some( mut actor ) = affected.and_then( |t| self.entities.actors.get_mut( t ) ) {
+          actor.effects.apply( kind, duration );
+        }
+      },
+      SpellEffect::Blink => {
+        let free = self.movement_cost( e, pos ).is_some() && !self.occupancy.is_occupied( pos );
+        
+        if free && self.map.visible_from( from, spell.range ).contains( &pos ) {
+          self.occupancy.move_actor( from, pos, e );
+          self.entities.positions.insert( e, pos );
+          
+          self.emit( Event::Moved { entity: e, from: from, to: pos } );
+          self.check_for_theft( e, from, pos );
+        }
+      },
+      SpellEffect::Summon( ref kind ) => {
+        // Whatever answers the call fights on the caster's side
+        for spot in ::pathfind::neighbours( &self.map, pos ) {
+          if let Some( summoned ) = self.spawn_monster( kind, spot ) {
+            self.join_faction( summoned, e );
+            break;
+          }
+        }
+      }
+    }
+  }
+  
+  // Pays for the item, taking it from the shop floor if it's still lying
+  // there
+  fn buy( &mut self, e : Entity, item : Entity ) {
+    if !self.entities.for_sale.has( item ) {
+      return
+    }
+    
+    let price = self.entities.prices.get( item ).map( |p| p.0 ).unwrap_or( 0 );
+    
+    let paid = match self.entities.purses.get_mut( e ) {
+      Some( ref mut purse ) if purse.gold >= price => {
+        purse.gold -= price;
+        true
+      },
+      _ => false
+    };
+    
+    if !paid {
+      return
+    }
+    
+    self.entities.for_sale.remove( item );
+    
+    if self.entities.positions.remove( item ).is_some() {
+      if let Some( mut inventory ) = self.entities.inventories.get_mut( e ) {
+        inventory.items.push( item );
+      }
+    }
+    
+    self.emit( Event::Bought { entity: e, item: item, price: price } );
+  }
+  
+  // Hands the item over to the shopkeeper, who puts it up for sale where
+  // the seller stands
+  fn sell( &mut self, e : Entity, item : Entity, keeper : Entity ) {
+    let price = match self.entities.prices.get( item ) {
+      Some( price ) => price.selling(),
+      None => return
+    };
+    
+    let pos = self.entities.position( e ).expect( "actor has no position" );
+    
+    if let Some( mut inventory ) = self.entities.inventories.get_mut( e ) {
+      inventory.items.retain( |&i| i != item );
+    }
+    
+    if let Some( mut purse ) = self.entities.purses.get_mut( e ) {
+      purse.gold += price;
+    }
+    
+    self.entities.positions.insert( item, pos );
+    self.entities.for_sale.insert( item, ForSale { keeper: keeper } );
+    
+    self.emit( Event::Sold { entity: e, item: item, price: price } );
+  }
+  
+  // Turns every shopkeeper whose shop the entity just left with unpaid
+  // items on it. The items are the thief's to keep from then on, as are
+  // those of shopkeepers who are no more.
+  fn check_for_theft( &mut self, e : Entity, from : Position, to : Position ) {
+    let carried = self.entities.inventories.get( e )
+      .map( |i| i.items.clone() )
+      .unwrap_or( Vec::new() );
+    
+    for item in carried {
+      let keeper = match self.entities.for_sale.get( item ) {
+        Some( for_sale ) => for_sale.keeper,
+        None => continue
+      };
+      
+      if !self.is_alive( keeper ) {
+        self.entities.for_sale.remove( item );
+        continue;
+      }
+      
+      let left = self.entities.shopkeepers.get( keeper )
+        .map( |s| s.area.contains( from ) && !s.area.contains( to ) )
+        .unwrap_or( false );
+      
+      if !left {
+        continue;
+      }
+      
+      self.entities.for_sale.remove( item );
+      
+      if !self.are_hostile( e, keeper ) {
+        let faction = Faction::from_config( ANGRY_SHOPKEEPER_FACTION )
+          .expect( "the faction of angry shopkeepers is missing" );
+        
+        self.entities.factions.insert( keeper, faction );
+        
+        if let Some( mut ai ) = self.entities.ais.get_mut( keeper ) {
+          ai.behaviour = Behaviour::Hunt;
+          ai.asleep = false;
+        }
+        
+        self.emit( Event::Robbed { entity: e, keeper: keeper } );
+      }
+    }
+  }
+  
+  // The first item of the given kind the entity carries
+  fn carried_item_of_kind( &self, e : Entity, kind : &str ) -> Option<Entity> {
+    let desc_id = format!( "item.{}", kind );
+    
+    self.entities.inventories.get( e ).and_then( |inventory| {
+      inventory.items.iter().cloned()
+        .find( |&item| self.kind_of( item ).desc_id == desc_id )
+    } )
+  }
+  
+  // Puts a new item of the given kind into the entity's inventory
+  fn give_item( &mut self, e : Entity, kind : &str ) -> Entity {
+    let item = self.spawn_item( kind, Position::new( 0, 0 ) );
+    self.entities.positions.remove( item );
+    
+    if let Some( mut inventory ) = self.entities.inventories.get_mut( e ) {
+      inventory.items.push( item );
+    }
+    
+    item
+  }
+  
+  fn start_quest( &mut self, quest : QuestId ) {
+    if self.journal.start( quest ) {
+      self.emit( Event::QuestStarted { quest: quest } );
+    }
+  }
+  
+  /// Moves the player's quests along with what happened in the world,
+  /// starting quests whose trigger was met and handing out the rewards of
+  /// completed ones.
+  pub fn track_quests( &mut self, events : &[Event] ) {
+    let player = self.player();
+    
+    for event in events {
+      for id in quest_ids() {
+        let quest = quest( id );
+        
+        if self.journal.status( id ) == QuestStatus::NotStarted {
+          let triggered = match quest.trigger {
+            Some( ref trigger ) =>
+              self.objective_gain( player, trigger, event ) >= trigger.target(),
+            None => false
+          };
+          
+          if !triggered {
+            continue;
+          }
+          
+          self.start_quest( id );
+        }
+        
+        if self.journal.status( id ) != QuestStatus::Active {
+          continue;
+        }
+        
+        for (i, objective) in quest.objectives.iter().enumerate() {
+          let gain = self.objective_gain( player, &objective.goal, event );
+          
+          if gain > 0 && self.journal.advance( id, i, gain ) {
+            self.emit( Event::ObjectiveCompleted { quest: id, objective: i } );
+          }
+        }
+        
+        if self.journal.complete_if_done( id ) {
+          self.emit( Event::QuestCompleted { quest: id } );
+          
+          for kind in quest.reward.iter() {
+            let item = self.give_item( player, kind );
+            self.emit( Event::Rewarded { entity: player, item: item } );
+          }
+        }
+      }
+    }
+  }
+  
+  // How far the event brings `e` towards the objective. Objectives about the
+  // state of the world are checked again with every event.
+  fn objective_gain( &self, e : Entity, objective : &Objective, event : &Event ) -> u32 {
+    match *objective {
+      Objective::Kill( ref kind, _ ) => match *event {
+        Event::Died { entity, killer: Some( killer ) }
+          if ( killer == e || self.is_follower_of( killer, e ) )
+          && self.kind_of( entity ).desc_id == format!( "monster.{}", kind ) => 1,
+        _ => 0
+      },
+      Objective::Fetch( ref kind ) =>
+        if self.carried_item_of_kind( e, kind ).is_some() { 1 } else { 0 },
+      Objective::ReachDepth( depth ) =>
+        if self.map.depth >= depth { 1 } else { 0 }
+    }
+  }
+  
+  /// Whether the condition of a dialogue choice holds for the entity
+  /// talking.
+  pub fn meets_condition( &self, e : Entity, condition : &Condition ) -> bool {
+    match *condition {
+      Condition::HasItem( ref kind ) => self.carried_item_of_kind( e, kind ).is_some(),
+      Condition::Quest( quest, status ) => self.journal.status( quest ) == status
+    }
+  }
+  
+  /// Carries out the effect of a dialogue choice picked by `e` while
+  /// talking to `speaker`.
+  pub fn apply_dialogue_effect( &mut self, e : Entity, speaker : Entity
+                              , effect : &DialogueEffect ) {
+    match *effect {
+      DialogueEffect::GiveItem( ref kind ) => {
+        let item = self.give_item( e, kind );
+        self.emit( Event::Received { entity: e, item: item, from: speaker } );
+      },
+      DialogueEffect::TakeItem( ref kind ) => {
+        let item = match self.carried_item_of_kind( e, kind ) {
+          Some( item ) => item,
+          None => return
+        };
+        
+        if let Some( mut inventory ) = self.entities.inventories.get_mut( e ) {
+          inventory.items.retain( |&i| i != item );
+        }
+        
+        self.emit( Event::HandedOver { entity: e, item: item, to: speaker } );
+        self.queue_despawn( item );
+      },
+      DialogueEffect::StartQuest( quest ) => self.start_quest( quest ),
+      DialogueEffect::ChangeFaction( ref name ) => {
+        let faction = Faction::from_config( name )
+          .expect( &format!( "Unknown faction `{}`", name ) );
+        
+        self.entities.factions.insert( speaker, faction );
+        
+        if self.are_hostile( e, speaker ) {
+          if let Some( mut ai ) = self.entities.ais.get_mut( speaker ) {
+            ai.behaviour = Behaviour::Hunt;
+          }
+          
+          self.emit( Event::TurnedHostile { entity: speaker } );
+        }
+      }
+    }
+  }
+  
+  /// Learns to recognize every item of the given kind.
+  pub fn identify( &mut self, kind : &str ) {
+    self.identified.insert( kind.to_string() );
+    
+    let entities = self.entities.unidentified.iter()
+      .filter( |&(_, u)| u.borrow().kind == kind )
+      .map( |(e, _)| e )
+      .collect::<Vec<_>>();
+    
+    for e in entities {
+      self.entities.unidentified.remove( e );
+    }
+  }
+  
+  // Moves the actor to a random free spot on the map
+  fn teleport( &mut self, e : Entity ) {
+    let from = self.entities.position( e ).expect( "actor has no position" );
+    
+    if let Some( to ) = self.random_free_position( e ) {
+      self.occupancy.move_actor( from, to, e );
+      self.entities.positions.insert( e, to );
+      self.check_for_theft( e, from, to );
+    }
+  }
+  
+  // Picks a random tile on the map that the actor could stand on
+  fn random_free_position( &self, e : Entity ) -> Option<Position> {
+    use ::tcod::random::Rng;
+    
+    let rng = Rng::get_instance();
+    
+    // Give up eventually, in case the map has no room left
+    for _ in 0..1000 {
+      let pos = Position::new( rng.get_int( 0, self.map.width as i32 - 1 ) as u32
+                             , rng.get_int( 0, self.map.height as i32 - 1 ) as u32 );
+      
+      if self.movement_cost( e, pos ).is_some() && !self.occupancy.is_occupied( pos ) {
+        return Some( pos )
+      }
+    }
+    
+    None
+  }
+  
+  /// Whether the entity's wounds can close up by themselves. They don't on
+  /// an empty stomach.
+  pub fn can_heal( &self, e : Entity ) -> bool {
+    self.entities.hunger.get( e )
+      .map( |h| h.stage().allows_healing() )
+      .unwrap_or( true )
+  }
+  
+  /// The time it takes the entity to step onto `pos`, as a percentage of a
+  /// normal step, or `None` if it can't go there.
+  pub fn movement_cost( &self, e : Entity, pos : Position ) -> Option<u32> {
+    let movement = self.entities.actors.get( e )
+      .map( |a| a.movement )
+      .unwrap_or( MovementType::Walking );
+    
+    self.map.tile_at( pos ).movement_cost( movement )
+  }
+  
+  /// The cost of passing through `pos` for the entity when planning a path,
+  /// like `movement_cost`. Closed doors count as passable, taking an extra
+  /// step to open, and traps that have been found are avoided if possible.
+  pub fn path_cost( &self, e : Entity, pos : Position ) -> Option<u32> {
+    let movement = self.entities.actors.get( e )
+      .map( |a| a.movement )
+      .unwrap_or( MovementType::Walking );
+    
+    // Hidden traps look like the floor they're in
+    let tile = self.map.tile_at( pos );
+    
+    if let Tile::Trap( _ ) = tile {
+      return tile.movement_cost( movement ).map( |_| KNOWN_TRAP_PATH_COST )
+    }
+    
+    match tile.interact( Interaction::Open ) {
+      Some( opened ) => opened.movement_cost( movement ).map( |c| c + NORMAL_MOVEMENT_COST ),
+      None => tile.movement_cost( movement )
+    }
+  }
+  
+  /// The stats of the entity, including the bonuses of its equipment.
+  pub fn effective_stats( &self, e : Entity ) -> Stats {
+    let mut stats = *self.entities.stats.get( e ).expect( "entity has no stats" );
+    
+    let items = match self.entities.equipment.get( e ) {
+      Some( equipment ) => equipment.items(),
+      None => return stats
+    };
+    
+    let mut speed = stats.speed as i32;
+    
+    for item in items {
+      if let Some( equip ) = self.entities.equippables.get( item ) {
+        stats.attack += equip.attack;
+        stats.defense += equip.defense;
+        speed += equip.speed;
+      }
+    }
+    
+    // Nothing can make an actor act instantly
+    stats.speed = if speed < 1 { 1 } else { speed as u32 };
+    
+    if let Some( hunger ) = self.entities.hunger.get( e ) {
+      stats.speed += stats.speed * hunger.stage().slowdown() / 100;
+    }
+    
+    stats
+  }
+  
+  /// Makes `attacker` hit `target`, killing it if it runs out of health.
+  pub fn attack( &mut self, attacker : Entity, target : Entity ) {
+    if !self.skill_check( attacker, SkillKind::Melee ) {
+      self.emit( Event::Missed { attacker: attacker, target: target } );
+      
+      return
+    }
+    
+    let attack = self.effective_stats( attacker ).attack;
+    
+    self.hit( attacker, target, attack );
+  }
+  
+  // Deals damage of the given strength from `attacker` to `target`, killing
+  // it if it runs out of health
+  fn hit( &mut self, attacker : Entity, target : Entity, attack : u32 ) {
+    if !self.entities.stats.has( target ) {
+      return
+    }
+    
+    let damage = {
+      let damage = self.effective_stats( target ).damage_taken_from( attack );
+      let mut target_stats = self.entities.stats.get_mut( target ).expect( "target has no stats" );
+      
+      target_stats.health = target_stats.health.saturating_sub( damage );
+      
+      damage
+    };
+    
+    self.emit( Event::Attacked { attacker: attacker, target: target, damage: damage } );
+    
+    // Nobody sleeps through getting hurt
+    let woke = match self.entities.ais.get_mut( target ) {
+      Some( mut ai ) if ai.asleep => {
+        ai.asleep = false;
+        true
+      },
+      _ => false
+    };
+    
+    if woke {
+      self.emit( Event::WokeUp { entity: target } );
+    }
+    
+    if !self.is_alive( target ) {
+      self.kill( target, Some( attacker ) );
+    }
+  }
+  
+  // Deals damage to the entity that doesn't come from anyone, killing it if
+  // it runs out of health
+  fn hurt( &mut self, e : Entity, damage : u32 ) {
+    match self.entities.stats.get_mut( e ) {
+      Some( mut stats ) => stats.health = stats.health.saturating_sub( damage ),
+      None => return
+    }
+    
+    if !self.is_alive( e ) {
+      self.kill( e, None );
+    }
+  }
+  
+  /// Removes a dead actor from play. The player is left in place, so the
+  /// game can show what happened.
+  pub fn kill( &mut self, e : Entity, killer : Option<Entity> ) {
+    self.emit( Event::Died { entity: e, killer: killer } );
+    
+    let reward = self.entities.rewards.get( e ).map( |r| r.points );
+    
+    if let (Some( killer ), Some( points )) = (killer, reward) {
+      self.gain_experience( killer, points );
+    }
+    
+    if self.entities.player_controlled.has( e ) {
+      return
+    }
+    
+    if let Some( pos ) = self.entities.position( e ) {
+      self.occupancy.remove( pos, e );
+    }
+    
+    self.entities.actors.remove( e );
+    self.entities.ais.remove( e );
+    self.entities.graphics.remove( e );
+    self.entities.lights.remove( e );
+    self.queue_despawn( e );
+  }
+  
+  // Gives the entity experience, levelling it up if it's gathered enough
+  fn gain_experience( &mut self, e : Entity, points : u32 ) {
+    let (gained, level) = match self.entities.experience.get_mut( e ) {
+      Some( mut experience ) => (experience.gain( points ), experience.level),
+      None => return
+    };
+    
+    if gained == 0 {
+      return
+    }
+    
+    if let Some( mut stats ) = self.entities.stats.get_mut( e ) {
+      let health = health_per_level() * gained;
+      
+      stats.max_health += health;
+      stats.health += health;
+    }
+    
+    self.emit( Event::LevelledUp { entity: e, level: level } );
+  }
+  
+  /// Applies one of the stat increases the entity earned by levelling up.
+  pub fn increase_stat( &mut self, e : Entity, increase : StatIncrease ) {
+    {
+      let mut experience = self.entities.experience.get_mut( e ).expect( "entity has no experience" );
+      
+      assert!( experience.pending > 0, "no stat increase is left to pick" );
+      experience.pending -= 1;
+    }
+    
+    let mut stats = self.entities.stats.get_mut( e ).expect( "entity has no stats" );
+    
+    match increase {
+      StatIncrease::Attack => stats.attack += 1,
+      StatIncrease::Defense => stats.defense += 1,
+      StatIncrease::Health => {
+        stats.max_health += HEALTH_INCREASE;
+        stats.health += HEALTH_INCREASE;
+      },
+      StatIncrease::Speed =>
+        stats.speed = if stats.speed > SPEED_INCREASE { stats.speed - SPEED_INCREASE } else { 1 }
+    }
+  }
+  
+  pub fn is_alive( &self, e : Entity ) -> bool {
+    self.entities.stats.get( e ).map( |s| s.health > 0 ).unwrap_or( false )
+  }
+  
+  /// The items lying on the ground at the given position.
+  pub fn items_at( &self, pos : Position ) -> Vec<Entity> {
+    self.entities.items.iter()
+      .map( |(e, _)| e )
+      .filter( |&e| self.entities.position( e ) == Some( pos ) )
+      .collect()
+  }
+  
+  /// The name of the entity as the player knows it.
+  pub fn name_of( &self, e : Entity ) -> String {
+    match self.entities.unidentified.get( e ) {
+      Some( unidentified ) => unidentified.name.clone(),
+      None => self.kind_of( e ).description().name().to_string()
+    }
+  }
+  
+  /// The entity controlled by the player.
+  pub fn player( &self ) -> Entity {
+    self.entities.player_controlled.iter().next()
+      .map( |(e, _)| e )
+      .expect( "there's no player in the world" )
+  }
+  
+  pub fn kind_of( &self, e : Entity ) -> Ref<Kind> {
+    self.entities.kinds.get( e ).expect( "entity has no kind" )
+  }
+  
+  /// Returns the actor standing at the given position.
+  pub fn actor_at( &self, pos : Position ) -> Option<Entity> {
+    self.occupancy.at( pos )
+  }
+  
+  /// Returns the actors within `radius` tiles of `pos`.
+  pub fn actors_within( &self, pos : Position, radius : u32 ) -> Vec<Entity> {
+    self.occupancy.within_radius( pos, radius ).into_iter()
+      .map( |(e, _)| e )
+      .collect()
+  }
+  
+  /// Whether the two actors would fight each other, going by their
+  /// factions. Actors without a faction don't fight anyone.
+  pub fn are_hostile( &self, a : Entity, b : Entity ) -> bool {
+    let factions = &self.entities.factions;
+    
+    match (factions.get( a ), factions.get( b )) {
+      (Some( fa ), Some( fb )) => a != b && fa.is_hostile_to( *fb ),
+      _ => false
+    }
+  }
+  
+  /// Whether `follower` follows `leader` around.
+  pub fn is_follower_of( &self, follower : Entity, leader : Entity ) -> bool {
+    self.entities.ais.get( follower )
+      .map( |ai| ai.leader == Some( leader ) )
+      .unwrap_or( false )
+  }
+  
+  /// Finds the closest actor within `radius` tiles that's hostile towards
+  /// the given actor.
+  pub fn nearest_hostile( &self, e : Entity, radius : u32 ) -> Option<Entity> {
+    let pos = match self.entities.position( e ) {
+      Some( pos ) => pos,
+      None => return None
+    };
+    
+    self.occupancy.nearest( pos, radius, |other| self.are_hostile( e, other ) )
+  }
+  
+  fn shortest_action_duration( &mut self ) -> u32 {
+    self.entities.actors.iter().fold( 0, | sh, (_, ac) | {
       let actor = ac.borrow();
       
-      if actor.action.duration != 0 && actor.action.duration > sh {
+      if actor.action.duration != 0 && ( sh == 0 || actor.action.duration < sh ) {
         actor.action.duration
       } else {
         sh
@@ -74,25 +1468,365 @@ impl World {
   }
   
   pub fn player_is_performing_action( &self ) -> bool {
-    self.player.borrow().actor.action.is_active()
+    let player = self.player();
+    
+    self.is_alive( player )
+    && self.entities.actors.get( player )
+         .map( |a| a.action.is_active() )
+         .unwrap_or( false )
   }
   
-  pub fn render<C : Console>( &self, ctx : &mut C ) {
-    self.map.render( ctx );
+  /// Recomputes the light falling on every tile and what the player can
+  /// see.
+  pub fn update_lighting( &mut self ) {
+    self.lighting.clear();
     
-    /*
-    for item in self.items {
-      item.render( ctx );
+    for &(pos, light) in &self.map.lights {
+      self.lighting.add_light( &self.map, pos, light );
     }
-    */
     
-    for actor in &self.actors {
-      actor.borrow().render( ctx );
+    for (e, light) in self.entities.lights.iter() {
+      if let Some( pos ) = self.entities.position( e ) {
+        self.lighting.add_light( &self.map, pos, *light.borrow() );
+      }
     }
     
-    self.player.borrow().actor.render( ctx );
+    let player = self.player();
+    let pos = self.entities.position( player ).expect( "player has no position" );
+    
+    // A blind player only knows what's right next to them
+    let sight = if self.player_has( StatusKind::Blindness ) { 1 } else { 0 };
+    self.lighting.add_sight( &self.map, pos, sight );
+    
+    let mut seen = Vec::new();
+    
+    for y in 0..self.map.height {
+      for x in 0..self.map.width {
+        let pos = Position::new( x as u32, y as u32 );
+        
+        if self.can_see( pos ) {
+          seen.push( pos );
+        }
+      }
+    }
+    
+    for pos in seen {
+      self.map.explore( pos );
+    }
+  }
+  
+  fn player_has( &self, status : StatusKind ) -> bool {
+    self.entities.actors.get( self.player() )
+      .map( |a| a.effects.has( status ) )
+      .unwrap_or( false )
+  }
+  
+  /// Whether there's anyone hostile towards the player that the player can
+  /// see.
+  pub fn hostile_in_view( &self ) -> bool {
+    let player = self.player();
+    
+    if self.player_has( StatusKind::Blindness ) {
+      return false
+    }
+    
+    self.entities.actors.iter().any( |(e, _)| {
+      self.are_hostile( player, e )
+      && self.entities.position( e ).map( |p| self.can_see( p ) ).unwrap_or( false )
+    } )
+  }
+  
+  /// Whether the player can make out something at the given position.
+  pub fn can_see( &self, pos : Position ) -> bool {
+    self.lighting.is_visible( pos )
+    && self.lighting.brightness_at( pos ) >= MIN_VISIBLE_BRIGHTNESS
+  }
+  
+  pub fn render<C : Console>( &self, ctx : &mut C ) {
+    self.map.render( ctx, &self.lighting );
+    
+    let player = self.player();
+    let blind = self.player_has( StatusKind::Blindness );
+    
+    // Things lying around are drawn beneath the actors standing on them
+    let (actors, others) : (Vec<_>, Vec<_>) = self.entities.graphics.iter()
+      .partition( |&(e, _)| self.entities.actors.has( e ) );
+    
+    for (e, graphics) in others.into_iter().chain( actors.into_iter() ) {
+      if e == player {
+        continue;
+      }
+      
+      let pos = match self.entities.position( e ) {
+        Some( pos ) => pos,
+        None => continue
+      };
+      
+      // A blind player can't make out anything, and neither can anything be
+      // made out if it's in the dark
+      if !blind && self.can_see( pos ) {
+        graphics.borrow().render( ctx, pos, self.lighting.light_at( pos ) );
+      }
+    }
+    
+    if let Some( pos ) = self.entities.position( player ) {
+      let graphics = self.entities.graphics.get( player ).expect( "player has no graphics" );
+      graphics.render( ctx, pos, self.lighting.light_at( pos ) );
+    }
   }
 }
 
-pub struct Item {
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use description::load_descriptions;
+  use faction::load_factions;
+  use monster::load_monsters;
+  use item::load_items;
+  use equipment::EquipSlot;
+  use character::*;
+  use skill::load_skills;
+  use spell::load_spells;
+  
+  // Loads the game data the test map refers to
+  fn load_data() {
+    load_descriptions();
+    load_tile_graphics();
+    load_factions();
+    load_monsters();
+    load_items();
+    load_character_options();
+    load_skills();
+    load_spells();
+  }
+  
+  /// Builds a world from the test map.
+  pub fn test_world() -> World {
+    load_data();
+    
+    World::new( "data/test.toml", test_character() ).expect( "Failed to load the test map" )
+  }
+  
+  /// A character of the first class and background, without their starting
+  /// gear so tests can hand out their own.
+  pub fn test_character() -> Character {
+    let mut class = classes()[ 0 ].clone();
+    let mut background = backgrounds()[ 0 ].clone();
+    
+    for option in vec![ &mut class, &mut background ] {
+      option.equipment.clear();
+      option.items.clear();
+    }
+    
+    Character::new( "Tester".to_string(), class, background )
+  }
+  
+  /// Moves the entity straight to `pos`.
+  pub fn place( world : &mut World, e : Entity, pos : Position ) {
+    let from = world.entities.position( e ).expect( "entity has no position" );
+    
+    world.occupancy.move_actor( from, pos, e );
+    world.entities.positions.insert( e, pos );
+  }
+  
+  // The kinds of the items in the player's inventory and equipment
+  fn player_gear( world : &World ) -> (Vec<String>, Vec<String>) {
+    let player = world.player();
+    let kinds = |items : Vec<Entity>| {
+      items.into_iter().map( |i| world.kind_of( i ).desc_id.clone() ).collect::<Vec<_>>()
+    };
+    
+    let carried = world.entities.inventories.get( player ).expect( "player has no inventory" )
+      .items.clone();
+    let equipped = world.entities.equipment.get( player ).expect( "player has no equipment" )
+      .items();
+    
+    (kinds( carried ), kinds( equipped ))
+  }
+  
+  #[test]
+  fn player_starts_as_the_created_character() {
+    load_data();
+    
+    let mut class = classes()[ 0 ].clone();
+    class.equipment = vec![ "dagger".to_string() ];
+    class.items = vec![ "ration".to_string() ];
+    
+    let mut background = backgrounds()[ 0 ].clone();
+    background.equipment = vec![ "buckler".to_string() ];
+    background.items = vec![ "potion_of_healing".to_string() ];
+    
+    let character = Character::new( "Tester".to_string(), class, background );
+    let world = World::new( "data/test.toml", character.clone() )
+      .expect( "Failed to load the test map" );
+    let player = world.player();
+    
+    assert_eq!( *world.entities.stats.get( player ).expect( "player has no stats" )
+              , character.stats() );
+    assert_eq!( player_gear( &world )
+              , (vec![ "item.ration".to_string(), "item.potion_of_healing".to_string() ]
+                , vec![ "item.dagger".to_string(), "item.buckler".to_string() ]) );
+    
+    // Nobody needs to guess what their own things are
+    let potion = world.entities.inventories.get( player ).expect( "player has no inventory" ).items[ 1 ];
+    assert!( !world.entities.unidentified.has( potion ) );
+  }
+  
+  /// Gives the entity a new item of the given kind to carry.
+  pub fn carry( world : &mut World, e : Entity, kind : &str ) -> Entity {
+    let pos = world.entities.position( e ).expect( "entity has no position" );
+    let item = world.spawn_item( kind, pos );
+    
+    world.entities.positions.remove( item );
+    world.entities.inventories.get_mut( e ).expect( "entity has no inventory" ).items.push( item );
+    
+    item
+  }
+  
+  #[test]
+  fn carried_equipment_does_nothing() {
+    let mut world = test_world();
+    let player = world.player();
+    let base = world.effective_stats( player );
+    
+    carry( &mut world, player, "dagger" );
+    carry( &mut world, player, "leather_armour" );
+    
+    assert_eq!( world.effective_stats( player ), base );
+  }
+  
+  #[test]
+  fn equipment_adds_to_stats() {
+    let mut world = test_world();
+    let player = world.player();
+    let base = world.effective_stats( player );
+    
+    let dagger = carry( &mut world, player, "dagger" );
+    let armour = carry( &mut world, player, "leather_armour" );
+    world.equip( player, dagger, EquipSlot::Weapon );
+    world.equip( player, armour, EquipSlot::Body );
+    
+    let stats = world.effective_stats( player );
+    assert_eq!( stats.attack, base.attack + 2 );
+    assert_eq!( stats.defense, base.defense + 2 );
+    assert_eq!( stats.speed, base.speed + 10 );
+    assert_eq!( stats.health, base.health );
+    
+    // The bonuses are derived, the entity's own stats stay as they were
+    assert_eq!( *world.entities.stats.get( player ).expect( "player has no stats" ), base );
+  }
+  
+  #[test]
+  fn equipping_over_an_item_replaces_its_bonuses() {
+    let mut world = test_world();
+    let player = world.player();
+    let base = world.effective_stats( player );
+    
+    let dagger = carry( &mut world, player, "dagger" );
+    let bow = carry( &mut world, player, "bow" );
+    world.equip( player, dagger, EquipSlot::Weapon );
+    world.equip( player, bow, EquipSlot::Weapon );
+    
+    assert_eq!( world.effective_stats( player ).attack, base.attack );
+    assert!( world.entities.inventories.get( player ).expect( "player has no inventory" )
+               .items.contains( &dagger ) );
+  }
+  
+  #[test]
+  fn speed_bonuses_stack() {
+    let mut world = test_world();
+    let player = world.player();
+    let base = world.effective_stats( player );
+    
+    let left = carry( &mut world, player, "ring_of_swiftness" );
+    let right = carry( &mut world, player, "ring_of_swiftness" );
+    world.equip( player, left, EquipSlot::LeftRing );
+    world.equip( player, right, EquipSlot::RightRing );
+    
+    assert_eq!( world.effective_stats( player ).speed, base.speed - 30 );
+  }
+  
+  #[test]
+  fn healing_stops_at_max_health() {
+    let mut world = test_world();
+    let player = world.player();
+    let max_health = world.entities.stats.get( player ).expect( "player has no stats" ).max_health;
+    
+    world.entities.stats.get_mut( player ).expect( "player has no stats" ).health = max_health - 9;
+    let potion = carry( &mut world, player, "potion_of_healing" );
+    world.use_item( player, potion );
+    assert_eq!( world.entities.stats.get( player ).expect( "player has no stats" ).health
+              , max_health - 1 );
+    
+    let potion = carry( &mut world, player, "potion_of_healing" );
+    world.use_item( player, potion );
+    assert_eq!( world.entities.stats.get( player ).expect( "player has no stats" ).health
+              , max_health );
+  }
+  
+  #[test]
+  fn using_an_item_uses_it_up_and_identifies_its_kind() {
+    let mut world = test_world();
+    let player = world.player();
+    let potion = carry( &mut world, player, "potion_of_healing" );
+    assert!( world.entities.unidentified.has( potion ) );
+    
+    world.use_item( player, potion );
+    assert!( !world.entities.inventories.get( player ).expect( "player has no inventory" )
+               .items.contains( &potion ) );
+    
+    let another = carry( &mut world, player, "potion_of_healing" );
+    assert!( !world.entities.unidentified.has( another ) );
+  }
+  
+  #[test]
+  fn status_potions_apply_their_effect() {
+    let mut world = test_world();
+    let player = world.player();
+    let potion = carry( &mut world, player, "potion_of_speed" );
+    
+    world.use_item( player, potion );
+    assert!( world.entities.actors.get( player ).expect( "player is not an actor" )
+               .effects.has( StatusKind::Haste ) );
+  }
+  
+  #[test]
+  fn identify_reveals_everything_carried() {
+    let mut world = test_world();
+    let player = world.player();
+    let potion = carry( &mut world, player, "potion_of_confusion" );
+    let scroll = carry( &mut world, player, "scroll_of_identify" );
+    
+    world.use_item( player, scroll );
+    assert!( !world.entities.unidentified.has( potion ) );
+  }
+  
+  #[test]
+  fn magic_mapping_explores_the_whole_map() {
+    let mut world = test_world();
+    let player = world.player();
+    let corner = Position::new( world.map.width as u32 - 1, world.map.height as u32 - 1 );
+    assert!( !world.map.is_explored( corner ) );
+    
+    let scroll = carry( &mut world, player, "scroll_of_magic_mapping" );
+    world.use_item( player, scroll );
+    assert!( world.map.is_explored( corner ) );
+  }
+  
+  #[test]
+  fn shop_is_set_up_in_the_room_behind_its_door() {
+    let world = test_world();
+    let shop = Area { x: 11, y: 7, width: 7, height: 3 };
+    
+    let keepers = world.entities.shopkeepers.entities();
+    assert_eq!( keepers.len(), 1 );
+    assert_eq!( world.entities.position( keepers[ 0 ] ), Some( Position::new( 11, 8 ) ) );
+    assert_eq!( world.entities.shopkeepers.get( keepers[ 0 ] ).map( |k| k.area ), Some( shop ) );
+    
+    let goods = world.entities.for_sale.entities();
+    assert_eq!( goods.len(), world.map.shops[ 0 ].stock.len() );
+    assert!( goods.iter().all( |&item| {
+      world.entities.position( item ).map( |p| shop.contains( p ) ).unwrap_or( false )
+    } ) );
+  }
 }